@@ -1,77 +1,209 @@
 use crate::vec2::{Vec2u, Vec2i};
 
 use std::convert::TryFrom;
+use std::fmt;
+use std::ops::{Index, IndexMut};
 
-use std::iter::FlatMap;
-use std::slice::Iter;
+/** A cell which can be read from one character of a text grid */
+pub trait FromGridChar: Sized {
+    fn from_grid_char(c: char) -> Option<Self>;
+}
+
+/** A cell which can be written as one character of a text grid */
+pub trait ToGridChar {
+    fn to_grid_char(&self) -> char;
+}
+
+impl FromGridChar for char {
+    fn from_grid_char(c: char) -> Option<Self> {
+        Some(c)
+    }
+}
+
+impl ToGridChar for char {
+    fn to_grid_char(&self) -> char {
+        *self
+    }
+}
+
+/** Why text couldn't be parsed as a grid. Lines and columns count from 1. */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GridParseError {
+    Ragged { line: usize, len: usize, expected: usize },
+    UnknownChar { line: usize, column: usize, c: char },
+}
+
+impl fmt::Display for GridParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GridParseError::Ragged { line, len, expected } => {
+                write!(f, "Line {} is {} characters long, expected {}", line, len, expected)
+            }
+            GridParseError::UnknownChar { line, column, c } => {
+                write!(f, "Unrecognised character {:?} at line {}, column {}", c, line, column)
+            }
+        }
+    }
+}
 
+impl std::error::Error for GridParseError {}
+
+/** Which cells around a position count as its neighbours, and what happens at the edges */
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Neighbourhood {
+    offsets: &'static [(isize, isize)],
+    wrap: bool
+}
+
+impl Neighbourhood {
+    /** The cells sharing an edge, in reading order: up, left, right, down */
+    pub const FOUR: Self = Self {
+        offsets: &[(0, -1), (-1, 0), (1, 0), (0, 1)],
+        wrap: false
+    };
+
+    /** The cells sharing an edge or a corner, in reading order */
+    pub const EIGHT: Self = Self {
+        offsets: &[(-1, -1), (0, -1), (1, -1), (-1, 0), (1, 0), (-1, 1), (0, 1), (1, 1)],
+        wrap: false
+    };
+
+    /**
+     * Wrap around the edges as on a torus, rather than leaving out neighbours
+     * outside the grid. On grids less than 3 wide or high this can give the same
+     * cell more than once.
+     */
+    pub fn wrapping(self) -> Self {
+        Self { wrap: true, ..self }
+    }
+}
+
+/**
+ * A grid stored row by row in a single `Vec`, so the cell at (x, y) is
+ * `data[y * len_x + x]`.
+ */
 #[derive(Clone, PartialEq, Debug)]
 pub struct Array2D<T> {
     pub len_x: usize,
     pub len_y: usize,
-    pub data: Vec<Vec<T>>
+    pub data: Vec<T>
 }
 
-impl <T : std::fmt::Display> std::fmt::Display for Array2D<T> {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        for v in &self.data {
-            for c in v {
-                write!(f, "{}", c)?;
+impl <T : FromGridChar> Array2D<T> {
+    /**
+     * Parse a grid with one row per line and one cell per character. Blank
+     * lines at the end are ignored, but every other line must be as long as
+     * the first.
+     */
+    pub fn parse(text: &str) -> Result<Self, GridParseError> {
+        let mut lines: Vec<(usize, usize, &str)> = text.lines()
+            .enumerate()
+            .map(|(i, line)| (i + 1, 0, line.trim_end_matches('\r')))
+            .collect();
+        while lines.last().is_some_and(|(_, _, line)| line.is_empty()) {
+            lines.pop();
+        }
+        Self::parse_lines(lines)
+    }
+
+    /**
+     * As `parse`, but ignoring blank lines and whitespace around each line, for
+     * grids written out indented in the source.
+     */
+    pub fn parse_trimmed(text: &str) -> Result<Self, GridParseError> {
+        let lines = text.lines()
+            .enumerate()
+            .map(|(i, line)| {
+                let start = line.trim_start();
+                (i + 1, line[..line.len() - start.len()].chars().count(), start.trim_end())
+            })
+            .filter(|(_, _, line)| !line.is_empty())
+            .collect();
+        Self::parse_lines(lines)
+    }
+
+    /** Parse (line number, characters trimmed from the start, text) for each row */
+    fn parse_lines(lines: Vec<(usize, usize, &str)>) -> Result<Self, GridParseError> {
+        let len_y = lines.len();
+        let mut data = Vec::new();
+        let mut len_x = None;
+        for (line, indent, text) in lines {
+            let mut len = 0;
+            for (i, c) in text.chars().enumerate() {
+                let cell = T::from_grid_char(c)
+                    .ok_or(GridParseError::UnknownChar { line, column: indent + i + 1, c })?;
+                data.push(cell);
+                len += 1;
+            }
+            let expected = *len_x.get_or_insert(len);
+            if len != expected {
+                return Err(GridParseError::Ragged { line, len, expected });
             }
-            writeln!(f, "")?;
         }
-        Ok(())
+
+        Ok(Self {
+            len_x: len_x.unwrap_or(0),
+            len_y,
+            data
+        })
+    }
+}
+
+impl <T : ToGridChar> Array2D<T> {
+    /** The grid as text which `parse` reads back */
+    pub fn to_grid_string(&self) -> String {
+        let mut text = String::with_capacity((self.len_x + 1) * self.len_y);
+        for row in self.rows() {
+            text.extend(row.iter().map(ToGridChar::to_grid_char));
+            text.push('\n');
+        }
+        text
+    }
+}
+
+impl <T : ToGridChar> fmt::Display for Array2D<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.to_grid_string())
     }
 }
 
 impl <T : Clone> Array2D<T> {
     pub fn with_shape(len_x: usize, len_y: usize, init_val: &T) -> Self {
-        let mut v = Vec::new();
-        for y in 0..len_y {
-            let mut row = Vec::new();
-            for x in 0..len_x {
-                row.push(init_val.clone());
-            }
-            v.push(row);
+        Self {
+            len_x,
+            len_y,
+            data: vec![init_val.clone(); len_x * len_y]
         }
-
-        return Self {
-            len_x: len_x,
-            len_y: len_y,
-            data: v
-        };
     }
 }
 
 impl <T> Array2D<T> {
     pub fn from(data: Vec<Vec<T>>) -> Self {
-        if data.len() == 0 {
-            return Self {
-                len_x: 0,
-                len_y: 0,
-                data: data
-            };
-        }
-
-        let len_x = data[0].len();
+        let len_y = data.len();
+        let len_x = data.first().map_or(0, |row| row.len());
         for d in &data {
             assert_eq!(len_x, d.len());
         }
         Self {
-            len_x: len_x,
-            len_y: data.len(),
-            data: data
+            len_x,
+            len_y,
+            data: data.into_iter().flatten().collect()
         }
     }
 
-    pub fn get(&self, x: usize, y: usize) -> Option<&T> {
+    fn offset(&self, x: usize, y: usize) -> Option<usize> {
         if x < self.len_x && y < self.len_y {
-            Some(&self.data[y][x])
+            Some(y * self.len_x + x)
         } else {
             None
         }
     }
 
+    pub fn get(&self, x: usize, y: usize) -> Option<&T> {
+        let i = self.offset(x, y)?;
+        Some(&self.data[i])
+    }
+
     pub fn getv(&self, p: &Vec2u) -> Option<&T> {
         return self.get(p.x, p.y);
     }
@@ -85,15 +217,11 @@ impl <T> Array2D<T> {
     }
 
     pub fn get_mut(&mut self, x: usize, y: usize) -> Option<&mut T> {
-        if x < self.len_x && y < self.len_y {
-            Some(&mut self.data[y][x])
-        } else {
-            None
-        }
+        let i = self.offset(x, y)?;
+        Some(&mut self.data[i])
     }
 
     pub fn getv_mut(&mut self, p: &Vec2u) -> Option<&mut T> {
-
         return self.get_mut(p.x, p.y);
     }
 
@@ -106,29 +234,269 @@ impl <T> Array2D<T> {
     }
 
     pub fn position<F : Fn(&T) -> bool>(&self, f: F) -> Option<(usize, usize)> {
-        for y in 0..self.len_y {
-            for x in 0..self.len_x {
-                if f(&self.data[y][x]) {
-                    return Some((x, y));
-                }
+        self.enumerate().find(|(_, _, c)| f(c)).map(|(x, y, _)| (x, y))
+    }
+
+    pub fn row(&self, y: usize) -> &[T] {
+        &self.data[y * self.len_x..(y + 1) * self.len_x]
+    }
+
+    pub fn row_mut(&mut self, y: usize) -> &mut [T] {
+        &mut self.data[y * self.len_x..(y + 1) * self.len_x]
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = &[T]> {
+        (0..self.len_y).map(move |y| self.row(y))
+    }
+
+    /** The cells of column `x`, from top to bottom */
+    pub fn column(&self, x: usize) -> impl Iterator<Item = &T> {
+        assert!(x < self.len_x, "column {} out of range for width {}", x, self.len_x);
+        self.data.iter().skip(x).step_by(self.len_x)
+    }
+
+    pub fn column_mut(&mut self, x: usize) -> impl Iterator<Item = &mut T> {
+        assert!(x < self.len_x, "column {} out of range for width {}", x, self.len_x);
+        let len_x = self.len_x;
+        self.data.iter_mut().skip(x).step_by(len_x)
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, T> {
+        self.data.iter()
+    }
+
+    pub fn iter_mut(&mut self) -> std::slice::IterMut<'_, T> {
+        self.data.iter_mut()
+    }
+
+    pub fn neighbours4(&self, p: &Vec2u) -> impl Iterator<Item = Vec2u> {
+        self.neighbours(p, Neighbourhood::FOUR)
+    }
+
+    pub fn neighbours8(&self, p: &Vec2u) -> impl Iterator<Item = Vec2u> {
+        self.neighbours(p, Neighbourhood::EIGHT)
+    }
+
+    /** Positions of the neighbours of `p` within the grid */
+    pub fn neighbours(&self, p: &Vec2u, n: Neighbourhood) -> impl Iterator<Item = Vec2u> {
+        let (x, y) = (p.x as isize, p.y as isize);
+        let (w, h) = (self.len_x as isize, self.len_y as isize);
+        n.offsets.iter().filter_map(move |(dx, dy)| {
+            let (nx, ny) = (x + dx, y + dy);
+            if w == 0 || h == 0 {
+                None
+            } else if n.wrap {
+                Some(Vec2u::new(nx.rem_euclid(w) as usize, ny.rem_euclid(h) as usize))
+            } else if nx >= 0 && nx < w && ny >= 0 && ny < h {
+                Some(Vec2u::new(nx as usize, ny as usize))
+            } else {
+                None
             }
+        })
+    }
+
+    /**
+     * Positions of the neighbours of `p` within the grid, for code which walks
+     * the grid with signed positions. A `p` outside the grid has none.
+     */
+    pub fn neighboursvi<'a>(&'a self, p: &Vec2i, n: Neighbourhood) -> impl Iterator<Item = Vec2i> + 'a {
+        Vec2u::try_from(p.clone()).ok().into_iter()
+            .flat_map(move |p| self.neighbours(&p, n))
+            .map(|q| Vec2i::try_from(q).unwrap())
+    }
+
+    /** The neighbours of `p` with their contents */
+    pub fn neighbour_cells(&self, p: &Vec2u, n: Neighbourhood) -> impl Iterator<Item = (Vec2u, &T)> {
+        self.neighbours(p, n).map(move |q| {
+            let c = &self[&q];
+            (q, c)
+        })
+    }
+
+    /** Every cell with its coordinates, row by row */
+    pub fn enumerate(&self) -> impl Iterator<Item = (usize, usize, &T)> {
+        let len_x = self.len_x;
+        self.data.iter().enumerate().map(move |(i, c)| (i % len_x, i / len_x, c))
+    }
+}
+
+impl <T> Index<(usize, usize)> for Array2D<T> {
+    type Output = T;
+
+    fn index(&self, (x, y): (usize, usize)) -> &T {
+        match self.offset(x, y) {
+            Some(i) => &self.data[i],
+            None => panic!("({}, {}) out of range for {}x{} array", x, y, self.len_x, self.len_y),
+        }
+    }
+}
+
+impl <T> IndexMut<(usize, usize)> for Array2D<T> {
+    fn index_mut(&mut self, (x, y): (usize, usize)) -> &mut T {
+        match self.offset(x, y) {
+            Some(i) => &mut self.data[i],
+            None => panic!("({}, {}) out of range for {}x{} array", x, y, self.len_x, self.len_y),
         }
+    }
+}
 
-        return None;
+impl <T> Index<&Vec2u> for Array2D<T> {
+    type Output = T;
+
+    fn index(&self, p: &Vec2u) -> &T {
+        &self[(p.x, p.y)]
+    }
+}
+
+impl <T> IndexMut<&Vec2u> for Array2D<T> {
+    fn index_mut(&mut self, p: &Vec2u) -> &mut T {
+        &mut self[(p.x, p.y)]
     }
 }
 
-impl <T: Sized> Array2D<T> {
-    pub fn iter<'a>(&'a self) -> impl Iterator<Item = &'a T> {
-        self.data.iter().flat_map(|v| v.iter())
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn grid() -> Array2D<i32> {
+        Array2D::from(vec![vec![1, 2, 3], vec![4, 5, 6]])
+    }
+
+    #[test]
+    fn indexing() {
+        let mut g = grid();
+        assert_eq!(g.data, vec![1, 2, 3, 4, 5, 6]);
+        assert_eq!(g[(2, 0)], 3);
+        assert_eq!(g[&Vec2u::new(0, 1)], 4);
+        assert_eq!(g.get(3, 0), None);
+        assert_eq!(g.getvi(&Vec2i::new(-1, 0)), None);
+
+        g[(1, 1)] = 50;
+        g[&Vec2u::new(0, 0)] += 10;
+        assert_eq!(g.get(1, 1), Some(&50));
+        assert_eq!(g.rows().collect::<Vec<_>>(), vec![&[11, 2, 3][..], &[4, 50, 6][..]]);
     }
 
-    // TODO: This is interesting
-    // pub fn enumerate(&self) -> std::slice::Iter<&T> {
-    //     self.data.iter()
-    //         .enumerate()
-    //         .flat_map(|(y, v)| {
-    //             v.iter().enumerate().map(|(x, c)| (x, y, c))
-    //         })
-    // }
+    #[test]
+    #[should_panic(expected = "(3, 0) out of range for 3x2 array")]
+    fn index_past_row_end() {
+        // Would be (0, 1) if only the offset were checked
+        let _ = grid()[(3, 0)];
+    }
+
+    #[test]
+    fn slices_and_iterators() {
+        let mut g = grid();
+        assert_eq!(g.row(1), &[4, 5, 6]);
+        assert_eq!(g.column(1).collect::<Vec<_>>(), vec![&2, &5]);
+        assert_eq!(g.rows().count(), 2);
+
+        g.row_mut(0)[0] = 0;
+        for c in g.column_mut(2) {
+            *c *= 10;
+        }
+        for c in g.iter_mut() {
+            *c += 1;
+        }
+        assert_eq!(g.iter().cloned().collect::<Vec<_>>(), vec![1, 3, 31, 5, 6, 61]);
+
+        let cells: Vec<(usize, usize, i32)> = g.enumerate().map(|(x, y, c)| (x, y, *c)).collect();
+        assert_eq!(cells[2], (2, 0, 31));
+        assert_eq!(cells[4], (1, 1, 6));
+        assert_eq!(g.position(|c| *c == 6), Some((1, 1)));
+
+        let empty = Array2D::<i32>::from(vec![]);
+        assert_eq!(empty.enumerate().count(), 0);
+        assert_eq!(Array2D::<char>::from(vec![]).to_string(), "");
+    }
+
+    #[test]
+    fn neighbours() {
+        let g = Array2D::from(vec![vec![1, 2, 3], vec![4, 5, 6], vec![7, 8, 9]]);
+        let at = |x, y| Vec2u::new(x, y);
+        let values = |n: Neighbourhood, p: Vec2u| g.neighbour_cells(&p, n).map(|(_, c)| *c).collect::<Vec<_>>();
+
+        assert_eq!(g.neighbours4(&at(0, 0)).collect::<Vec<_>>(), vec![at(1, 0), at(0, 1)]);
+        assert_eq!(g.neighbours8(&at(1, 1)).count(), 8);
+        assert_eq!(values(Neighbourhood::FOUR, at(1, 1)), vec![2, 4, 6, 8]);
+        assert_eq!(values(Neighbourhood::EIGHT, at(2, 2)), vec![5, 6, 8]);
+
+        assert_eq!(values(Neighbourhood::FOUR.wrapping(), at(0, 0)), vec![7, 3, 2, 4]);
+        assert_eq!(values(Neighbourhood::EIGHT.wrapping(), at(2, 0)), vec![8, 9, 7, 2, 1, 5, 6, 4]);
+
+        let empty = Array2D::<i32>::from(vec![]);
+        assert_eq!(empty.neighbours(&at(0, 0), Neighbourhood::EIGHT.wrapping()).count(), 0);
+
+        let vi = |x, y| Vec2i::new(x, y);
+        assert_eq!(g.neighboursvi(&vi(0, 1), Neighbourhood::FOUR).collect::<Vec<_>>(), vec![vi(0, 0), vi(1, 1), vi(0, 2)]);
+        assert_eq!(g.neighboursvi(&vi(-1, 0), Neighbourhood::FOUR).count(), 0);
+    }
+
+    #[derive(Clone, Copy, PartialEq, Debug)]
+    enum Cell {
+        Open,
+        Wall
+    }
+
+    impl FromGridChar for Cell {
+        fn from_grid_char(c: char) -> Option<Self> {
+            match c {
+                '.' => Some(Cell::Open),
+                '#' => Some(Cell::Wall),
+                _ => None
+            }
+        }
+    }
+
+    impl ToGridChar for Cell {
+        fn to_grid_char(&self) -> char {
+            match self {
+                Cell::Open => '.',
+                Cell::Wall => '#'
+            }
+        }
+    }
+
+    #[test]
+    fn parse() {
+        let g = Array2D::<Cell>::parse("#.#\r\n..#\n\n").unwrap();
+        assert_eq!((g.len_x, g.len_y), (3, 2));
+        assert_eq!(g[(1, 0)], Cell::Open);
+        assert_eq!(g[(2, 1)], Cell::Wall);
+        assert_eq!(g.to_grid_string(), "#.#\n..#\n");
+        assert_eq!(Array2D::parse(&g.to_string()), Ok(g.clone()));
+
+        let indented = "
+            #.#
+            ..#
+        ";
+        assert_eq!(Array2D::parse_trimmed(indented), Ok(g));
+
+        let chars = Array2D::<char>::parse(" a \nb c").unwrap();
+        assert_eq!(chars.to_string(), " a \nb c\n");
+        assert_eq!(Array2D::<Cell>::parse("").unwrap().len_y, 0);
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(
+            Array2D::<Cell>::parse("#.#\n#.\n"),
+            Err(GridParseError::Ragged { line: 2, len: 2, expected: 3 })
+        );
+        // Blank lines are only ignored at the end
+        assert_eq!(
+            Array2D::<Cell>::parse("#.#\n\n#.#"),
+            Err(GridParseError::Ragged { line: 2, len: 0, expected: 3 })
+        );
+
+        let err = Array2D::<Cell>::parse("#.#\n#@#").unwrap_err();
+        assert_eq!(err, GridParseError::UnknownChar { line: 2, column: 2, c: '@' });
+        assert_eq!(err.to_string(), "Unrecognised character '@' at line 2, column 2");
+
+        // Columns count the trimmed indentation
+        assert_eq!(
+            Array2D::<Cell>::parse_trimmed("\n    ##\n    #x"),
+            Err(GridParseError::UnknownChar { line: 3, column: 6, c: 'x' })
+        );
+    }
 }
@@ -0,0 +1,535 @@
+use common::intcode::binary::{self, Image};
+use common::intcode::conformance::parse_suite;
+use common::intcode::coverage::Coverage;
+use common::intcode::dap;
+use common::intcode::diff::{diff, report};
+use common::intcode::disasm::{disassemble_at_with, disassemble_with};
+use common::intcode::heatmap::Heatmap;
+use common::intcode::smc::SelfModTracker;
+use common::intcode::symbols::Symbols;
+use common::intcode::validate::validate;
+use common::intcode::{IntCodeError, IntCodeIO, IntCodeMachine, IntCodeRunner, Word};
+use num::BigInt;
+
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{BufRead, BufWriter, Write};
+use std::sync::mpsc::{channel, Sender};
+
+const USAGE: &str = "Usage:
+    intcode run <program> [--ascii] [--bigint]
+    intcode disasm <program>
+    intcode pack <program> <output>
+    intcode diff <program> <program>
+    intcode check <program>
+    intcode trace <program> <trace-file> [--ascii] [--bigint]
+    intcode coverage <program> <report-file> [--ascii] [--bigint]
+    intcode heatmap <program> <image.ppm> [--ascii] [--bigint]
+    intcode debug <program> [--ascii] [--bigint]
+    intcode test <suite>...
+    intcode dap
+
+    Programs may be comma separated text or packed binary. Symbols are read
+    from a <program>.sym sidecar file when there is one
+    --bigint runs with arbitrary precision words instead of i64
+    heatmap draws 64 addresses per row: red for writes, green reads, blue executes
+    dap serves the Debug Adapter Protocol on stdin/stdout";
+
+/** Converts between the terminal and the machine in either numeric or ASCII mode */
+struct Console {
+    ascii: bool,
+}
+
+impl Console {
+    fn print_output<W: Word>(&self, v: W) {
+        let c = v.to_u8().filter(|c| *c < 128);
+        if let (true, Some(c)) = (self.ascii, c) {
+            print!("{}", c as char);
+        } else if self.ascii {
+            println!("\\[~{}]", v);
+        } else {
+            println!("{}", v);
+        }
+    }
+
+    /** Parse a line of user input and queue it for the machine */
+    fn send_line<W: Word>(&self, itx: &Sender<W>, line: &str) -> Result<(), String> {
+        if self.ascii {
+            for c in line.chars() {
+                itx.send(W::from(c as i64)).unwrap();
+            }
+            // Send the line feed it expects
+            itx.send(W::from(10)).unwrap();
+        } else {
+            for s in line.split(|c: char| c == ',' || c.is_whitespace()) {
+                if s.is_empty() {
+                    continue;
+                }
+                let v = s.parse::<W>().map_err(|_| format!("Invalid number '{}'", s))?;
+                itx.send(v).unwrap();
+            }
+        }
+        Ok(())
+    }
+
+    /** Read a line from stdin and queue it for the machine. Returns false on EOF */
+    fn read_input<W: Word>(&self, itx: &Sender<W>) -> bool {
+        loop {
+            if !self.ascii {
+                eprint!("input> ");
+            }
+            std::io::stdout().flush().unwrap();
+
+            let mut s = String::new();
+            if std::io::stdin().read_line(&mut s).unwrap() == 0 {
+                return false;
+            }
+
+            match self.send_line(itx, s.trim_end_matches(['\n', '\r'])) {
+                Ok(()) => return true,
+                Err(e) => eprintln!("{}", e),
+            }
+        }
+    }
+}
+
+/** Load a program along with its symbols. The sidecar overrides symbols packed into a binary */
+fn load<W: Word>(path: &str) -> Image<W> {
+    let contents = std::fs::read(path).unwrap_or_else(|e| panic!("Couldn't read {}: {}", path, e));
+    let mut image = binary::load(&contents).unwrap_or_else(|e| panic!("Couldn't load {}: {}", path, e));
+    let sidecar = Symbols::load_sidecar(path).unwrap_or_else(|e| panic!("{}", e));
+    for (addr, symbol) in sidecar.iter() {
+        image.symbols.insert(addr, symbol.clone());
+    }
+    image
+}
+
+fn save<W: Word>(path: &str, machine: &IntCodeMachine<W>, symbols: &Symbols) -> Result<(), String> {
+    let bytes = binary::encode(machine, symbols)?;
+    std::fs::write(path, bytes).map_err(|e| format!("Couldn't write {}: {}", path, e))
+}
+
+fn create(path: &str) -> File {
+    File::create(path).unwrap_or_else(|e| panic!("Couldn't create {}: {}", path, e))
+}
+
+/** Files to write the optional instrumentation of a run to */
+#[derive(Default)]
+struct Reports {
+    trace: Option<BufWriter<File>>,
+    coverage: Option<File>,
+    heatmap: Option<File>,
+}
+
+const HEATMAP_WIDTH: usize = 64;
+const HEATMAP_SCALE: usize = 4;
+
+/**
+ * Step the runner, writing the instruction it executes and any self modifying
+ * code it causes to `out`. `smc_reported` counts the events already written.
+ */
+fn traced_step<W: Word>(
+    out: &mut impl Write,
+    runner: &mut IntCodeRunner<W>,
+    symbols: &Symbols,
+    smc_reported: &mut usize,
+) -> Result<Option<IntCodeIO<W>>, IntCodeError> {
+    // An instruction waiting for input was written when it was first reached
+    if !runner.expects_input() {
+        let m = &runner.machine;
+        let mut line = disassemble_at_with(m, m.pc(), symbols);
+        if let Some(label) = line.label.take() {
+            writeln!(out, "{}:", label).unwrap();
+        }
+        writeln!(out, "rb={:<6} {}", m.rb(), line).unwrap();
+    }
+
+    let result = runner.try_step();
+
+    if let Some(smc) = &runner.smc {
+        for event in &smc.events[*smc_reported..] {
+            writeln!(out, "SMC: {}", event).unwrap();
+        }
+        *smc_reported = smc.events.len();
+    }
+
+    result
+}
+
+fn run<W: Word>(image: Image<W>, console: &Console, reports: Reports) {
+    let Reports {
+        mut trace,
+        coverage,
+        heatmap,
+    } = reports;
+    let (itx, irx) = channel::<W>();
+    let program = image.machine.clone();
+    let mut runner = IntCodeRunner::new(image.machine, irx);
+    if coverage.is_some() {
+        runner.coverage = Some(Coverage::new());
+    }
+    if trace.is_some() {
+        runner.smc = Some(SelfModTracker::new());
+    }
+    if heatmap.is_some() {
+        runner.heatmap = Some(Heatmap::new());
+    }
+    let mut smc_reported = 0;
+
+    loop {
+        let result = match &mut trace {
+            Some(out) => traced_step(out, &mut runner, &image.symbols, &mut smc_reported),
+            None => runner.try_step(),
+        };
+
+        match result {
+            Err(e) => {
+                std::io::stdout().flush().unwrap();
+                eprintln!("\n{}", e);
+                std::process::exit(2);
+            }
+            Ok(None) => {}
+            Ok(Some(IntCodeIO::Finished)) => break,
+            Ok(Some(IntCodeIO::Input)) if !console.read_input(&itx) => {
+                eprintln!("End of input while program awaits input");
+                break;
+            }
+            Ok(Some(IntCodeIO::Input)) => {}
+            Ok(Some(IntCodeIO::Output(v))) => console.print_output(v),
+        }
+    }
+
+    std::io::stdout().flush().unwrap();
+
+    if let (Some(mut out), Some(cov)) = (coverage, &runner.coverage) {
+        write!(out, "{}", cov.report(&program)).unwrap();
+    }
+    if let (Some(mut out), Some(map)) = (heatmap, &runner.heatmap) {
+        // Addresses past the end of memory are all zero, so aren't worth drawing
+        let len = runner.machine.memory().len();
+        out.write_all(&map.to_ppm(len, HEATMAP_WIDTH, HEATMAP_SCALE)).unwrap();
+    }
+}
+
+fn print_registers<W: Word>(runner: &IntCodeRunner<W>) {
+    let m = &runner.machine;
+    println!("pc={} rb={} awaiting_input={}", m.pc(), m.rb(), runner.expects_input());
+}
+
+fn parse_num(s: Option<&str>, default: usize) -> Result<usize, String> {
+    match s {
+        None => Ok(default),
+        Some(s) => s.parse().map_err(|_| format!("Invalid number '{}'", s)),
+    }
+}
+
+/** An address given as a number or a symbol name */
+fn parse_addr(s: Option<&str>, symbols: &Symbols, default: usize) -> Result<usize, String> {
+    match s {
+        None => Ok(default),
+        Some(s) => symbols.resolve(s).ok_or_else(|| format!("Unknown address '{}'", s)),
+    }
+}
+
+const DEBUG_HELP: &str = "Commands:
+    s [n]            step n instructions (default 1)
+    c                continue until breakpoint, halt or input is needed
+    b <addr>         toggle breakpoint at address
+    r                show registers
+    m <addr> [len]   show memory
+    l [addr] [n]     list n instructions from addr (default pc)
+    i <input>        queue input for the program
+    n [pc] <addr> <name>  name an address, marking it as code with pc
+    a <addr> <comment>    annotate an address
+    w <file>         save a binary snapshot of the machine
+    q                quit
+
+Addresses may be given by name. Names and comments are saved to the sidecar file";
+
+fn debug<W: Word>(image: Image<W>, program: &str, console: &Console) {
+    let (itx, irx) = channel::<W>();
+    let mut runner = IntCodeRunner::new(image.machine, irx);
+    let mut symbols = image.symbols;
+    let mut breakpoints = HashSet::<usize>::new();
+    let mut finished = false;
+
+    println!("{}", DEBUG_HELP);
+    print_registers(&runner);
+    println!("{}", disassemble_at_with(&runner.machine, runner.machine.pc(), &symbols));
+
+    let stdin = std::io::stdin();
+    loop {
+        print!("(dbg) ");
+        std::io::stdout().flush().unwrap();
+
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line).unwrap() == 0 {
+            break;
+        }
+
+        let mut words = line.split_whitespace();
+        let cmd = if let Some(c) = words.next() { c } else { continue };
+
+        let result: Result<(), String> = (|| {
+            match cmd {
+                "s" | "c" => {
+                    if finished {
+                        return Err("Program has finished".to_string());
+                    }
+                    let limit = if cmd == "s" { parse_num(words.next(), 1)? } else { usize::MAX };
+
+                    for i in 0..limit {
+                        if i > 0 && breakpoints.contains(&runner.machine.pc()) {
+                            println!("Breakpoint at {}", runner.machine.pc());
+                            break;
+                        }
+
+                        match runner.try_step() {
+                            Err(e) => {
+                                println!("\n{}", e);
+                                finished = true;
+                                break;
+                            }
+                            Ok(None) => {}
+                            Ok(Some(IntCodeIO::Output(v))) => console.print_output(v),
+                            Ok(Some(IntCodeIO::Input)) => {
+                                println!("\nProgram is awaiting input");
+                                break;
+                            }
+                            Ok(Some(IntCodeIO::Finished)) => {
+                                println!("\nProgram finished");
+                                finished = true;
+                                break;
+                            }
+                        }
+                    }
+
+                    std::io::stdout().flush().unwrap();
+                    print_registers(&runner);
+                    println!("{}", disassemble_at_with(&runner.machine, runner.machine.pc(), &symbols));
+                }
+                "b" => {
+                    let addr = parse_addr(words.next(), &symbols, runner.machine.pc())?;
+                    if breakpoints.remove(&addr) {
+                        println!("Removed breakpoint at {}", addr);
+                    } else {
+                        breakpoints.insert(addr);
+                        println!("Added breakpoint at {}", addr);
+                    }
+                }
+                "r" => print_registers(&runner),
+                "m" => {
+                    let addr = parse_addr(words.next(), &symbols, runner.machine.pc())?;
+                    let len = parse_num(words.next(), 8)?;
+                    for a in addr..addr + len {
+                        println!("{:>6}: {}", a, runner.machine.load(a));
+                    }
+                }
+                "l" => {
+                    let mut addr = parse_addr(words.next(), &symbols, runner.machine.pc())?;
+                    let count = parse_num(words.next(), 10)?;
+                    for _ in 0..count {
+                        let line = disassemble_at_with(&runner.machine, addr, &symbols);
+                        let marker = if breakpoints.contains(&addr) { "*" } else { " " };
+                        println!("{}{}", marker, line);
+                        addr += line.raw.len();
+                    }
+                }
+                "i" => {
+                    let rest = line.trim_start()[1..].trim_start();
+                    console.send_line(&itx, rest.trim_end_matches(['\n', '\r']))?;
+                }
+                "w" => {
+                    let path = words.next().ok_or("Expected a file name")?;
+                    save(path, &runner.machine, &symbols)?;
+                    println!("Saved snapshot to {}", path);
+                }
+                "n" => {
+                    let mut arg = words.next();
+                    let code = arg == Some("pc");
+                    if code {
+                        arg = words.next();
+                    }
+                    let addr = parse_addr(arg, &symbols, runner.machine.pc())?;
+                    let name = words.next().ok_or("Expected a name")?;
+                    symbols.set_name(addr, name, code)?;
+                    symbols.save_sidecar(program)?;
+                }
+                "a" => {
+                    let addr = parse_addr(words.next(), &symbols, runner.machine.pc())?;
+                    let comment = words.collect::<Vec<&str>>().join(" ");
+                    if comment.is_empty() {
+                        return Err("Expected a comment".to_string());
+                    }
+                    symbols.set_comment(addr, &comment);
+                    symbols.save_sidecar(program)?;
+                }
+                "q" => std::process::exit(0),
+                _ => return Err(DEBUG_HELP.to_string()),
+            }
+            Ok(())
+        })();
+
+        if let Err(e) = result {
+            println!("{}", e);
+        }
+    }
+}
+
+/** Run conformance suites, returning true if every case passed */
+fn test(suites: &[&str]) -> bool {
+    let mut passed = 0;
+    let mut failed = 0;
+    for path in suites {
+        let contents = std::fs::read_to_string(path)
+            .unwrap_or_else(|e| panic!("Couldn't read {}: {}", path, e));
+        let cases = match parse_suite(&contents) {
+            Ok(cases) => cases,
+            Err(e) => {
+                println!("{}: {}", path, e);
+                failed += 1;
+                continue;
+            }
+        };
+
+        for case in cases {
+            match case.run() {
+                Ok(()) => {
+                    println!("PASS {}", case.name);
+                    passed += 1;
+                }
+                Err(e) => {
+                    println!("FAIL {} ({}:{})", case.name, path, case.line);
+                    for line in e.lines() {
+                        println!("    {}", line);
+                    }
+                    failed += 1;
+                }
+            }
+        }
+    }
+
+    println!("\n{} passed, {} failed", passed, failed);
+    failed == 0
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let ascii = args.iter().any(|a| a == "--ascii");
+    let bigint = args.iter().any(|a| a == "--bigint");
+    let args: Vec<&str> = args
+        .iter()
+        .map(|a| a.as_str())
+        .filter(|a| !a.starts_with("--"))
+        .collect();
+    let console = Console { ascii };
+
+    match (args.as_slice(), bigint) {
+        (["run", program], false) => run(load::<i64>(program), &console, Reports::default()),
+        (["run", program], true) => run(load::<BigInt>(program), &console, Reports::default()),
+        (["disasm", program], _) => {
+            let image = load::<BigInt>(program);
+            for line in disassemble_with(&image.machine, &image.symbols) {
+                println!("{}", line);
+            }
+        }
+        (["pack", program, output], _) => {
+            let image = load::<BigInt>(program);
+            if let Err(e) = save(output, &image.machine, &image.symbols) {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+        (["diff", a, b], _) => {
+            print!("{}", report(&diff(&load::<BigInt>(a).machine, &load::<BigInt>(b).machine)));
+        }
+        (["check", program], _) => {
+            let image = load::<BigInt>(program);
+            let report = validate(&image.machine, &image.symbols);
+            println!("{}", report);
+            if !report.is_ok() {
+                std::process::exit(1);
+            }
+        }
+        (["trace", program, trace_file], _) => {
+            let reports = Reports {
+                trace: Some(BufWriter::new(create(trace_file))),
+                ..Default::default()
+            };
+            if bigint {
+                run(load::<BigInt>(program), &console, reports);
+            } else {
+                run(load::<i64>(program), &console, reports);
+            }
+        }
+        (["coverage", program, report_file], _) => {
+            let reports = Reports {
+                coverage: Some(create(report_file)),
+                ..Default::default()
+            };
+            if bigint {
+                run(load::<BigInt>(program), &console, reports);
+            } else {
+                run(load::<i64>(program), &console, reports);
+            }
+        }
+        (["heatmap", program, image_file], _) => {
+            let reports = Reports {
+                heatmap: Some(create(image_file)),
+                ..Default::default()
+            };
+            if bigint {
+                run(load::<BigInt>(program), &console, reports);
+            } else {
+                run(load::<i64>(program), &console, reports);
+            }
+        }
+        (["debug", program], false) => debug(load::<i64>(program), program, &console),
+        (["debug", program], true) => debug(load::<BigInt>(program), program, &console),
+        (["test", suites @ ..], _) if !suites.is_empty() => {
+            if !test(suites) {
+                std::process::exit(1);
+            }
+        }
+        (["dap"], _) => {
+            let stdin = std::io::BufReader::new(std::io::stdin());
+            dap::serve(stdin, std::io::stdout());
+        }
+        _ => {
+            eprintln!("{}", USAGE);
+            std::process::exit(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn trace_input() {
+        // Echoes its input, which it stores over its own first instruction
+        let (itx, irx) = channel();
+        let mut runner = IntCodeRunner::new(IntCodeMachine::load_file("3,0,4,0,99".to_string()), irx);
+        runner.smc = Some(SelfModTracker::new());
+
+        let mut out = Vec::new();
+        let mut smc_reported = 0;
+        loop {
+            match traced_step(&mut out, &mut runner, &Symbols::default(), &mut smc_reported).unwrap() {
+                Some(IntCodeIO::Input) => itx.send(42).unwrap(),
+                Some(IntCodeIO::Finished) => break,
+                _ => {}
+            }
+        }
+
+        // The IN is only written once, although it's stepped again once given input
+        let trace = String::from_utf8(out).unwrap();
+        assert_eq!(trace, "\
+rb=0           0: 3,0                          IN  [0]
+SMC: pc 0 wrote to 0 which was already executed
+rb=0           2: 4,0                          OUT [0]
+rb=0           4: 99                           HLT
+");
+    }
+}
@@ -0,0 +1,141 @@
+/*!
+ * Finding where a deterministic system starts repeating itself. Starting from
+ * an initial state and applying `step` over and over, every system with
+ * finitely many states eventually loops, though perhaps only after a run of
+ * states it never returns to.
+ */
+
+use std::collections::HashMap;
+use std::hash::Hash;
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Cycle<S> {
+    /** Steps from the initial state to the first state on the loop */
+    pub start: usize,
+    /** Steps to go round the loop once */
+    pub length: usize,
+    /** The first state on the loop, which is the first state seen twice */
+    pub state: S,
+}
+
+impl<S> Cycle<S> {
+    /** The earliest step after which the state is the same as after `n` steps */
+    pub fn reduce(&self, n: usize) -> usize {
+        if n < self.start {
+            n
+        } else {
+            self.start + (n - self.start) % self.length
+        }
+    }
+}
+
+/**
+ * Find the cycle with Brent's algorithm, which only keeps a couple of states
+ * at a time but steps through the sequence about three times over
+ */
+pub fn brent<S: Clone + PartialEq, F: FnMut(&S) -> S>(initial: S, mut step: F) -> Cycle<S> {
+    // Find the length, by leaving the tortoise at each power of two steps until
+    // the hare catches up with it
+    let mut power = 1;
+    let mut length = 1;
+    let mut tortoise = initial.clone();
+    let mut hare = step(&initial);
+    while tortoise != hare {
+        if power == length {
+            tortoise = hare.clone();
+            power *= 2;
+            length = 0;
+        }
+        hare = step(&hare);
+        length += 1;
+    }
+
+    // Then with the hare a whole loop ahead, they meet at the start of the loop
+    let mut tortoise = initial.clone();
+    let mut hare = initial;
+    for _ in 0..length {
+        hare = step(&hare);
+    }
+    let mut start = 0;
+    while tortoise != hare {
+        tortoise = step(&tortoise);
+        hare = step(&hare);
+        start += 1;
+    }
+
+    Cycle { start, length, state: tortoise }
+}
+
+/**
+ * Find the cycle by remembering every state seen, which steps through the
+ * sequence only once
+ */
+pub fn hashed<S: Clone + Hash + Eq, F: FnMut(&S) -> S>(initial: S, mut step: F) -> Cycle<S> {
+    let mut seen = HashMap::new();
+    let mut state = initial;
+    let mut i = 0;
+    loop {
+        if let Some(first) = seen.get(&state) {
+            return Cycle { start: *first, length: i - first, state };
+        }
+        let next = step(&state);
+        seen.insert(state, i);
+        state = next;
+        i += 1;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /** The cycle found by stepping one state at a time and searching back */
+    fn naive(initial: u32, step: impl Fn(&u32) -> u32) -> Cycle<u32> {
+        let mut states = vec![initial];
+        loop {
+            let next = step(states.last().unwrap());
+            if let Some(start) = states.iter().position(|s| *s == next) {
+                return Cycle { start, length: states.len() - start, state: next };
+            }
+            states.push(next);
+        }
+    }
+
+    #[test]
+    fn against_naive() {
+        for m in 1..60 {
+            for a in 0..m {
+                for x in 0..m {
+                    let step = |s: &u32| (s * s + a) % m;
+                    let expected = naive(x, step);
+                    assert_eq!(brent(x, step), expected, "x -> x^2 + {} mod {} from {}", a, m, x);
+                    assert_eq!(hashed(x, step), expected, "x -> x^2 + {} mod {} from {}", a, m, x);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn shapes() {
+        // A loop with a tail: 0, 1, 2, 3, 4, 5, 6, 3, ...
+        let tail = |s: &u32| if *s == 6 { 3 } else { s + 1 };
+        let cycle = brent(0, tail);
+        assert_eq!((cycle.start, cycle.length, cycle.state), (3, 4, 3));
+        assert_eq!(cycle.reduce(2), 2);
+        assert_eq!(cycle.reduce(7), 3);
+        assert_eq!(cycle.reduce(1_000_000_001), 5);
+
+        // Starting on the loop
+        let cycle = hashed(5u32, |s| (s + 3) % 7);
+        assert_eq!((cycle.start, cycle.length, cycle.state), (0, 7, 5));
+
+        // A fixed point
+        let cycle = brent(100u32, |s| s / 2);
+        assert_eq!((cycle.start, cycle.length, cycle.state), (7, 1, 0));
+        assert_eq!(hashed(100u32, |s| s / 2), cycle);
+
+        // States which are only Clone + PartialEq work with Brent
+        let cycle = brent(vec![1.0, 0.0], |v| vec![-v[1], v[0]]);
+        assert_eq!((cycle.start, cycle.length), (0, 4));
+    }
+}
@@ -1,101 +1,241 @@
-use std::collections::VecDeque;
+use num::traits::{CheckedAdd, CheckedMul, One, ToPrimitive, Zero};
 use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
 use std::sync::mpsc::Receiver;
+use std::sync::Arc;
 
-#[derive(Clone, Copy)]
-enum Parameter {
-    Position(i64),
-    Direct(i64),
-    Relative(i64),
+pub mod binary;
+pub mod conformance;
+pub mod coverage;
+pub mod dap;
+pub mod diff;
+pub mod disasm;
+pub mod heatmap;
+pub mod opcodes;
+pub mod optimise;
+pub mod scheduler;
+pub mod smc;
+pub mod stream;
+pub mod symbols;
+pub mod validate;
+
+use coverage::Coverage;
+use heatmap::Heatmap;
+use opcodes::{Builtin, Effect, Opcode, OpcodeTable};
+use smc::SelfModTracker;
+use symbols::Symbols;
+
+/**
+ * A memory word of an intcode machine.
+ * `i64` is the default; `num::BigInt` gives an arbitrary precision machine
+ * for programs which deliberately need large numbers.
+ */
+pub trait Word:
+    Clone + Ord + fmt::Display + fmt::Debug + FromStr + From<i64> + Zero + One + CheckedAdd + CheckedMul + ToPrimitive
+{
+}
+
+impl<T> Word for T where
+    T: Clone + Ord + fmt::Display + fmt::Debug + FromStr + From<i64> + Zero + One + CheckedAdd + CheckedMul + ToPrimitive
+{
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum IntCodeError {
+    Overflow { pc: usize, ins: String },
+    InvalidAddress { pc: usize, ins: String },
+    InvalidMode { pc: usize, opcode: String },
 }
 
-impl Parameter {
-    fn value(&self, machine: &IntCodeMachine) -> i64 {
+impl fmt::Display for IntCodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IntCodeError::Overflow { pc, ins } => write!(f, "Arithmetic overflow at pc {}: {}", pc, ins),
+            IntCodeError::InvalidAddress { pc, ins } => write!(f, "Invalid address at pc {}: {}", pc, ins),
+            IntCodeError::InvalidMode { pc, opcode } => {
+                write!(f, "Invalid parameter mode at pc {}: opcode {}", pc, opcode)
+            }
+        }
+    }
+}
+
+impl std::error::Error for IntCodeError {}
+
+#[derive(Clone)]
+enum Parameter<W> {
+    Position(W),
+    Direct(W),
+    Relative(W),
+}
+
+impl<W: Word> Parameter<W> {
+    fn value(&self, machine: &IntCodeMachine<W>) -> Option<W> {
         if let Parameter::Direct(v) = self {
-            *v
+            Some(v.clone())
         } else {
-            machine.load(self.address(&machine))
+            Some(machine.load(self.address(machine)?))
         }
     }
 
-    fn address(&self, machine: &IntCodeMachine) -> usize {
+    fn address(&self, machine: &IntCodeMachine<W>) -> Option<usize> {
         match self {
-            Parameter::Position(p) => usize::try_from(*p).expect("Invalid address usize"),
-            Parameter::Direct(v) => panic!("Expected address, got direct mode value"),
+            Parameter::Position(p) => p.to_usize(),
+            Parameter::Direct(_) => None,
             Parameter::Relative(r) => {
-                usize::try_from((machine.rb as i64) + r).expect("Invalid address usize")
+                usize::try_from((machine.rb as i64).checked_add(r.to_i64()?)?).ok()
             }
         }
     }
 }
 
-enum Ops {
-    Add(Parameter, Parameter, Parameter), // (a, b, c) : c <- a + b
-    Mul(Parameter, Parameter, Parameter),
-    Input(Parameter),                          // Store input
-    Output(Parameter),                         // Output value/address
-    JumpNz(Parameter, Parameter),              // (a, b) : if a!=0 jump b
-    JumpEz(Parameter, Parameter),              // if a==0 jump b
-    LessThan(Parameter, Parameter, Parameter), // if a < b then c <- 1 else c <- 0
-    Equals(Parameter, Parameter, Parameter),   // if a == b then c <- 1 else c <- 0
-    AddRb(Parameter),                          // Add to the relative base
-    Noop(),
+impl<W: Word> fmt::Display for Parameter<W> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Parameter::Position(p) => write!(f, "[{}]", p),
+            Parameter::Direct(v) => write!(f, "{}", v),
+            Parameter::Relative(r) => write!(f, "[rb{:+}]", r),
+        }
+    }
+}
+
+/** A decoded instruction: what it does, and the parameters it was given */
+#[derive(Clone)]
+struct Ins<W> {
+    op: Arc<Opcode<W>>,
+    params: Vec<Parameter<W>>,
+}
+
+impl<W: Word> Parameter<W> {
+    /** As displayed, but with addresses named in `symbols`. `target` marks a jump destination */
+    fn symbolic(&self, symbols: &Symbols, target: bool) -> String {
+        let name = |v: &W| v.to_usize().and_then(|a| symbols.name(a));
+        match self {
+            Parameter::Position(p) => match name(p) {
+                Some(n) => format!("[{}]", n),
+                None => self.to_string(),
+            },
+            Parameter::Direct(v) if target => name(v).map(|n| n.to_string()).unwrap_or_else(|| self.to_string()),
+            _ => self.to_string(),
+        }
+    }
+}
+
+impl<W> Ins<W> {
+    fn builtin(&self) -> Option<Builtin> {
+        self.op.builtin()
+    }
+
+    fn len(&self) -> usize {
+        1 + self.params.len()
+    }
+
+    fn format(&self, params: Vec<String>) -> String {
+        if params.is_empty() {
+            self.op.name().to_string()
+        } else {
+            format!("{:<3} {}", self.op.name(), params.join(", "))
+        }
+    }
+}
+
+impl<W: Word> Ins<W> {
+    /** Disassembly text naming any addresses found in `symbols` */
+    fn symbolic(&self, symbols: &Symbols) -> String {
+        let jump = matches!(self.builtin(), Some(Builtin::JumpNz | Builtin::JumpEz));
+        let params = self
+            .params
+            .iter()
+            .enumerate()
+            .map(|(i, p)| p.symbolic(symbols, jump && i == 1))
+            .collect();
+        self.format(params)
+    }
+}
+
+impl<W: Word> fmt::Display for Ins<W> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let params = self.params.iter().map(|p| p.to_string()).collect();
+        write!(f, "{}", self.format(params))
+    }
 }
 
 #[derive(Clone)]
-pub struct IntCodeMachine {
-    ops: Vec<i64>,
+pub struct IntCodeMachine<W = i64> {
+    ops: Vec<W>,
     pc: usize,
     rb: usize,
+    opcodes: Arc<OpcodeTable<W>>,
 }
 
 impl IntCodeMachine {
-    fn new(ops: Vec<i64>) -> Self {
+    pub fn load_file(contents: String) -> Self {
+        Self::from_text(&contents)
+    }
+}
+
+impl<W: Word> IntCodeMachine<W> {
+    fn new(ops: Vec<W>) -> Self {
         Self {
             ops: ops,
             pc: 0,
             rb: 0,
+            opcodes: Arc::new(OpcodeTable::default()),
         }
     }
 
-    pub fn load_file(contents: String) -> Self {
-        let ops: Vec<i64> = contents
+    /** Use the instructions of a dialect in place of the standard ones */
+    pub fn with_opcodes(mut self, opcodes: OpcodeTable<W>) -> Self {
+        self.opcodes = Arc::new(opcodes);
+        self
+    }
+
+    pub fn opcodes(&self) -> &OpcodeTable<W> {
+        &self.opcodes
+    }
+
+    /** Parse a comma separated program into a machine of any word type */
+    pub fn from_text(contents: &str) -> Self {
+        Self::try_from_text(contents).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_from_text(contents: &str) -> Result<Self, String> {
+        let ops = contents
             .split(",")
             .map(|s| s.trim())
-            .map(|s| s.parse::<i64>().unwrap())
-            .collect();
-        Self::new(ops)
+            .map(|s| s.parse::<W>().map_err(|_| format!("Invalid intcode word '{}'", s)))
+            .collect::<Result<Vec<W>, String>>()?;
+        Ok(Self::new(ops))
     }
 
-    fn next(&mut self) -> i64 {
-        let c = self.ops[self.pc];
-        self.pc += 1;
-        return c;
+    pub fn pc(&self) -> usize {
+        self.pc
     }
 
-    fn params(&mut self, mode: &Vec<i64>, count: i64) -> Vec<Parameter> {
-        let mut p = Vec::with_capacity(count as usize);
-        for i in 0..count {
-            let c = self.next();
-            if i >= (mode.len() as i64) || mode[i as usize] == 0 {
-                p.push(Parameter::Position(c));
-            } else if mode[i as usize] == 1 {
-                p.push(Parameter::Direct(c));
-            } else if mode[i as usize] == 2 {
-                p.push(Parameter::Relative(c));
-            } else {
-                panic!("Invalid parameter mode {}", mode[i as usize]);
-            }
-        }
+    pub fn rb(&self) -> usize {
+        self.rb
+    }
 
-        return p;
+    pub fn memory(&self) -> &[W] {
+        &self.ops
     }
 
-    fn parse_ins(&mut self) -> Ops {
-        let opcode = self.next();
+    /** Decode the instruction at `addr` without executing it.
+     *  Returns the instruction and its length, or None if a parameter mode is invalid */
+    fn decode(&self, addr: usize) -> Option<(Ins<W>, usize)> {
+        let halt = || Some((Ins { op: self.opcodes.halt().clone(), params: Vec::new() }, 1));
+        let opcode = match self.load(addr).to_i64() {
+            Some(opcode) => opcode,
+            None => return halt(),
+        };
         let op = opcode % 100;
+        // Words which aren't in the opcode table halt the machine
+        let entry = match usize::try_from(op).ok().and_then(|op| self.opcodes.get(op)) {
+            Some(entry) => entry,
+            None => return halt(),
+        };
 
-        // Collect parameter modes as list of 0/1s indicating mode
+        // Collect parameter modes as list of 0/1/2s indicating mode
         let mut p_mode = Vec::new();
         let mut p = (opcode - op) / 100;
         while p > 0 {
@@ -104,177 +244,393 @@ impl IntCodeMachine {
             p = (p - d) / 10;
         }
 
-        // Parse op code
-        return match op {
-            1 => {
-                let p = self.params(&p_mode, 3);
-                Ops::Add(p[0], p[1], p[2])
-            }
-            2 => {
-                let p = self.params(&p_mode, 3);
-                Ops::Mul(p[0], p[1], p[2])
-            }
-            3 => Ops::Input(self.params(&p_mode, 1)[0]),
-            4 => Ops::Output(self.params(&p_mode, 1)[0]),
-            5 => {
-                let p = self.params(&p_mode, 2);
-                Ops::JumpNz(p[0], p[1])
-            }
-            6 => {
-                let p = self.params(&p_mode, 2);
-                Ops::JumpEz(p[0], p[1])
-            }
-            7 => {
-                let p = self.params(&p_mode, 3);
-                Ops::LessThan(p[0], p[1], p[2])
-            }
-            8 => {
-                let p = self.params(&p_mode, 3);
-                Ops::Equals(p[0], p[1], p[2])
-            }
-            9 => Ops::AddRb(self.params(&p_mode, 1)[0]),
-            _ => Ops::Noop(),
-        };
+        let count = entry.params().len();
+        let mut params = Vec::with_capacity(count);
+        for i in 0..count {
+            let c = self.load(addr + 1 + i);
+            params.push(match p_mode.get(i) {
+                None | Some(0) => Parameter::Position(c),
+                Some(1) => Parameter::Direct(c),
+                Some(2) => Parameter::Relative(c),
+                Some(_) => return None,
+            });
+        }
+
+        let ins = Ins { op: entry.clone(), params };
+        let len = ins.len();
+        Some((ins, len))
+    }
+
+    /** As `decode`, reporting an invalid parameter mode as an error */
+    fn try_decode(&self, pc: usize) -> Result<(Ins<W>, usize), IntCodeError> {
+        self.decode(pc).ok_or_else(|| IntCodeError::InvalidMode {
+            pc,
+            opcode: self.load(pc).to_string(),
+        })
+    }
+
+    fn parse_ins(&mut self) -> Result<Ins<W>, IntCodeError> {
+        let (ins, len) = self.try_decode(self.pc)?;
+        self.pc += len;
+        Ok(ins)
+    }
+
+    fn ins_text(&self, pc: usize) -> String {
+        match self.decode(pc) {
+            Some((ins, _)) => ins.to_string(),
+            None => format!("DATA {}", self.load(pc)),
+        }
+    }
+
+    fn value(&self, p: &Parameter<W>, pc: usize) -> Result<W, IntCodeError> {
+        p.value(self).ok_or_else(|| self.invalid_address(pc))
+    }
+
+    fn address(&self, p: &Parameter<W>, pc: usize) -> Result<usize, IntCodeError> {
+        p.address(self).ok_or_else(|| self.invalid_address(pc))
+    }
+
+    fn jump_target(&self, p: &Parameter<W>, pc: usize) -> Result<usize, IntCodeError> {
+        self.value(p, pc)?.to_usize().ok_or_else(|| self.invalid_address(pc))
+    }
+
+    fn overflow(&self, pc: usize) -> IntCodeError {
+        IntCodeError::Overflow {
+            pc,
+            ins: self.ins_text(pc),
+        }
     }
 
-    pub fn store(&mut self, addr: usize, val: i64) {
+    fn invalid_address(&self, pc: usize) -> IntCodeError {
+        IntCodeError::InvalidAddress {
+            pc,
+            ins: self.ins_text(pc),
+        }
+    }
+
+    pub fn store(&mut self, addr: usize, val: W) {
         if addr >= self.ops.len() {
-            self.ops.resize(addr + 1, 0);
+            self.ops.resize(addr + 1, W::zero());
         }
         self.ops[addr] = val;
     }
 
-    fn load(&self, addr: usize) -> i64 {
+    pub fn load(&self, addr: usize) -> W {
         if addr >= self.ops.len() {
-            0
+            W::zero()
         } else {
-            self.ops[addr]
+            self.ops[addr].clone()
         }
     }
 }
 
 #[derive(Debug)]
-pub enum IntCodeIO {
+pub enum IntCodeIO<W = i64> {
     Finished,
     Input,
-    Output(i64)
+    Output(W),
 }
 
-impl IntCodeIO {
-    pub fn unwrap_output(&self) -> i64 {
+impl<W: Word> IntCodeIO<W> {
+    pub fn unwrap_output(&self) -> W {
         if let Self::Output(i) = self {
-            *i
+            i.clone()
         } else {
             panic!("Cannot unwrap output for {:?}", self);
         }
     }
 }
 
-pub struct IntCodeRunner {
-    pub machine: IntCodeMachine,
+/**
+ * The state an instruction executes with: its parameters, the machine and the
+ * runner's input. Its effects on the machine are applied once it has finished.
+ */
+pub struct Exec<'a, W> {
+    machine: &'a IntCodeMachine<W>,
+    pc: usize,
+    params: &'a [Parameter<W>],
+    inputs: &'a Receiver<W>,
+    block_on_input: bool,
+    /** Input received while the instruction was waiting for it */
+    input: Option<W>,
+    /** Addresses read through the parameters */
+    reads: Vec<usize>,
+    /** Stores made by the instruction */
+    writes: Vec<(usize, W)>,
+    rb: Option<usize>,
+}
+
+impl<'a, W: Word> Exec<'a, W> {
+    /** Address of the instruction being executed */
+    pub fn pc(&self) -> usize {
+        self.pc
+    }
+
+    pub fn machine(&self) -> &IntCodeMachine<W> {
+        self.machine
+    }
+
+    /** The value of parameter `i`, according to its mode */
+    pub fn value(&mut self, i: usize) -> Result<W, IntCodeError> {
+        let p = &self.params[i];
+        if let Some(addr) = p.address(self.machine) {
+            self.reads.push(addr);
+        }
+        self.machine.value(p, self.pc)
+    }
+
+    /** The value of parameter `i` as an address to jump to */
+    pub fn target(&mut self, i: usize) -> Result<usize, IntCodeError> {
+        self.value(i)?.to_usize().ok_or_else(|| self.invalid_address())
+    }
+
+    /** Store to the address given by parameter `i` */
+    pub fn store(&mut self, i: usize, val: W) -> Result<(), IntCodeError> {
+        let addr = self.machine.address(&self.params[i], self.pc)?;
+        self.writes.push((addr, val));
+        Ok(())
+    }
+
+    /**
+     * The next input value, or None if there isn't one yet, in which case the
+     * instruction should return `Effect::AwaitInput`
+     */
+    pub fn input(&mut self) -> Option<W> {
+        if let Some(v) = self.input.take() {
+            Some(v)
+        } else if self.block_on_input {
+            Some(self.inputs.recv().unwrap())
+        } else {
+            self.inputs.try_recv().ok()
+        }
+    }
+
+    /** The relative base, as it was before the instruction */
+    pub fn rb(&self) -> usize {
+        self.machine.rb
+    }
+
+    pub fn set_rb(&mut self, rb: usize) {
+        self.rb = Some(rb);
+    }
+
+    /** An overflow error for this instruction */
+    pub fn overflow(&self) -> IntCodeError {
+        self.machine.overflow(self.pc)
+    }
+
+    /** An invalid address error for this instruction */
+    pub fn invalid_address(&self) -> IntCodeError {
+        self.machine.invalid_address(self.pc)
+    }
+}
+
+pub struct IntCodeRunner<W = i64> {
+    pub machine: IntCodeMachine<W>,
     pub finished: bool,
     pub block_on_input: bool,
-    inputs: Receiver<i64>,
-    input_state: Option<usize>
+    pub coverage: Option<Coverage>,
+    pub smc: Option<SelfModTracker>,
+    pub heatmap: Option<Heatmap>,
+    /** The code an instruction halted the program with */
+    pub exit_code: Option<W>,
+    inputs: Receiver<W>,
+    /** Whether the instruction at pc is waiting for input */
+    awaiting_input: bool,
 }
 
-impl IntCodeRunner {
-    pub fn new(machine: IntCodeMachine, inputs: Receiver<i64>) -> Self {
+impl<W: Word> IntCodeRunner<W> {
+    pub fn new(machine: IntCodeMachine<W>, inputs: Receiver<W>) -> Self {
         Self {
             machine: machine,
             finished: false,
             block_on_input: false,
+            coverage: None,
+            smc: None,
+            heatmap: None,
+            exit_code: None,
             inputs: inputs,
-            input_state: None
+            awaiting_input: false,
         }
     }
 
     pub fn expects_input(&self) -> bool {
-        self.input_state.is_some()
+        self.awaiting_input
     }
 
     /** Run until next input instruction */
-    pub fn next(&mut self) -> IntCodeIO {
-        // If we were waiting for input, try parse
-        if let Some(r) = self.input_state {
+    pub fn next(&mut self) -> IntCodeIO<W> {
+        self.try_next().unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /** Run until next input instruction, stopping if the program faults */
+    pub fn try_next(&mut self) -> Result<IntCodeIO<W>, IntCodeError> {
+        loop {
+            if let Some(io) = self.try_step()? {
+                return Ok(io);
+            }
+        }
+    }
+
+    /** Execute a single instruction, returning any IO event it caused */
+    pub fn step(&mut self) -> Option<IntCodeIO<W>> {
+        self.try_step().unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /** Execute a single instruction, reporting overflow and bad addresses as errors */
+    pub fn try_step(&mut self) -> Result<Option<IntCodeIO<W>>, IntCodeError> {
+        let pc = self.machine.pc;
+
+        // An instruction waiting for input was recorded when it was first
+        // reached, and is only tried again once there is some
+        if self.awaiting_input {
             let v = if self.block_on_input {
                 self.inputs.recv().unwrap()
+            } else if let Ok(v) = self.inputs.try_recv() {
+                v
             } else {
-                if let Ok(v) = self.inputs.try_recv() {
-                    v
-                } else {
-                    return IntCodeIO::Input;
-                }
+                return Ok(Some(IntCodeIO::Input));
             };
-            
-            self.machine.store(r, v);
 
-            self.input_state = None;
+            self.awaiting_input = false;
+            let ins = self.machine.parse_ins()?;
+            return self.execute_with_input(pc, ins, Some(v));
         }
 
-        loop {
-            match self.machine.parse_ins() {
-                Ops::Add(a, b, r) => {
-                    let a = a.value(&self.machine);
-                    let b = b.value(&self.machine);
-                    let r = r.address(&self.machine);
-                    self.machine.store(r, a + b);
-                }
-                Ops::Mul(a, b, r) => {
-                    let a = a.value(&self.machine);
-                    let b = b.value(&self.machine);
-                    let r = r.address(&self.machine);
-                    self.machine.store(r, a * b);
-                }
-                Ops::Input(r) => {
-                    let r = r.address(&self.machine);
-
-                    let v = if self.block_on_input {
-                        self.inputs.recv().unwrap()
-                    } else {
-                        if let Ok(v) = self.inputs.try_recv() {
-                            v
-                        } else {
-                            self.input_state = Some(r);
-                            return IntCodeIO::Input;
-                        }
-                    };
-                    
-                    self.machine.store(r, v);
-                }
-                Ops::Output(r) => {
-                    return IntCodeIO::Output(r.value(&self.machine));
-                }
-                Ops::JumpNz(a, p) => {
-                    if a.value(&self.machine) != 0 {
-                        self.machine.pc = usize::try_from(p.value(&self.machine)).unwrap()
-                    }
-                }
-                Ops::JumpEz(a, p) => {
-                    if a.value(&self.machine) == 0 {
-                        self.machine.pc = usize::try_from(p.value(&self.machine)).unwrap()
-                    }
-                }
-                Ops::LessThan(a, b, r) => {
-                    let a = a.value(&self.machine);
-                    let b = b.value(&self.machine);
-                    let r = r.address(&self.machine);
-                    self.machine.store(r, if a < b { 1 } else { 0 });
-                }
-                Ops::Equals(a, b, r) => {
-                    let a = a.value(&self.machine);
-                    let b = b.value(&self.machine);
-                    let r = r.address(&self.machine);
-                    self.machine.store(r, if a == b { 1 } else { 0 });
-                }
-                Ops::AddRb(r) => {
-                    self.machine.rb =
-                        usize::try_from(self.machine.rb as i64 + r.value(&self.machine)).unwrap();
-                }
-                Ops::Noop() => {
-                    return IntCodeIO::Finished;
-                }
+        if self.finished {
+            return Ok(Some(IntCodeIO::Finished));
+        }
+
+        if let Some(coverage) = &mut self.coverage {
+            coverage.record(pc);
+        }
+
+        let ins = self.machine.parse_ins()?;
+        if let Some(smc) = &mut self.smc {
+            smc.record_execute(pc, ins.len());
+        }
+        if let Some(heatmap) = &mut self.heatmap {
+            heatmap.record_execute(pc, ins.len());
+        }
+
+        self.execute(pc, ins)
+    }
+
+    /** Execute `ins`, decoded from `pc`, once the machine's pc has moved past it */
+    fn execute(&mut self, pc: usize, ins: Ins<W>) -> Result<Option<IntCodeIO<W>>, IntCodeError> {
+        self.execute_with_input(pc, ins, None)
+    }
+
+    fn execute_with_input(&mut self, pc: usize, ins: Ins<W>, input: Option<W>) -> Result<Option<IntCodeIO<W>>, IntCodeError> {
+        let mut exec = Exec {
+            machine: &self.machine,
+            pc,
+            params: &ins.params,
+            inputs: &self.inputs,
+            block_on_input: self.block_on_input,
+            input,
+            reads: Vec::new(),
+            writes: Vec::new(),
+            rb: None,
+        };
+        let effect = ins.op.call(&mut exec)?;
+        let Exec { reads, writes, rb, .. } = exec;
+
+        if let Effect::AwaitInput = effect {
+            self.machine.pc = pc;
+            self.awaiting_input = true;
+            return Ok(Some(IntCodeIO::Input));
+        }
+
+        if let Some(heatmap) = &mut self.heatmap {
+            for addr in reads {
+                heatmap.record_read(addr);
+            }
+        }
+        for (addr, val) in writes {
+            self.write(pc, addr, val);
+        }
+        if let Some(rb) = rb {
+            self.machine.rb = rb;
+        }
+
+        Ok(match effect {
+            Effect::Continue | Effect::AwaitInput => None,
+            Effect::Jump(target) => {
+                self.machine.pc = target;
+                None
+            }
+            Effect::Output(v) => Some(IntCodeIO::Output(v)),
+            Effect::Halt(code) => {
+                self.finished = true;
+                self.exit_code = code;
+                Some(IntCodeIO::Finished)
             }
+        })
+    }
+
+    /** Store a value on behalf of the instruction at `pc` */
+    fn write(&mut self, pc: usize, addr: usize, val: W) {
+        if let Some(smc) = &mut self.smc {
+            smc.record_write(pc, addr);
+        }
+        if let Some(heatmap) = &mut self.heatmap {
+            heatmap.record_write(addr);
+        }
+        self.machine.store(addr, val);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use num::BigInt;
+    use std::sync::mpsc::channel;
+
+    fn outputs<W: Word>(machine: IntCodeMachine<W>) -> Result<Vec<W>, IntCodeError> {
+        let (_itx, irx) = channel::<W>();
+        let mut runner = IntCodeRunner::new(machine, irx);
+        let mut out = Vec::new();
+        while let IntCodeIO::Output(v) = runner.try_next()? {
+            out.push(v);
         }
+        Ok(out)
+    }
+
+    #[test]
+    fn large_numbers() {
+        let m = IntCodeMachine::load_file("1102,34915192,34915192,7,4,7,99,0".to_string());
+        assert_eq!(outputs(m), Ok(vec![1219070632396864]));
+    }
+
+    #[test]
+    fn mul_overflow() {
+        // Square 2^40 then output it
+        let program = "1002,9,1099511627776,9,1002,9,1099511627776,9,104,1,99";
+        let m = IntCodeMachine::load_file(program.to_string());
+        assert_eq!(outputs(m), Err(IntCodeError::Overflow {
+            pc: 4,
+            ins: "MUL [9], 1099511627776, [9]".to_string(),
+        }));
+
+        let program = "1002,11,1099511627776,11,1002,11,1099511627776,11,4,11,99,1";
+        let m = IntCodeMachine::<BigInt>::from_text(program);
+        let expected: BigInt = "1208925819614629174706176".parse().unwrap();
+        assert_eq!(outputs(m), Ok(vec![expected]));
+    }
+
+    #[test]
+    fn add_overflow() {
+        let m = IntCodeMachine::load_file("1101,9223372036854775807,1,0,99".to_string());
+        assert!(matches!(outputs(m), Err(IntCodeError::Overflow { pc: 0, .. })));
+    }
+
+    #[test]
+    fn negative_address() {
+        let m = IntCodeMachine::load_file("4,-1,99".to_string());
+        assert_eq!(outputs(m), Err(IntCodeError::InvalidAddress {
+            pc: 0,
+            ins: "OUT [-1]".to_string(),
+        }));
     }
 }
@@ -0,0 +1,273 @@
+/*!
+ * Compact binary container for intcode programs and machine snapshots.
+ *
+ * ```text
+ * magic    "\0ICB"
+ * version  1 byte
+ * memory   varint word count, then one zigzag varint per word
+ * sections tag byte followed by its payload, until the end of the file:
+ *          1 pc       varint
+ *          2 rb       varint
+ *          3 symbols  varint count, then per symbol: varint address, flags byte
+ *                     (1 = code), then name and comment as varint length and
+ *                     utf8 bytes, where an empty string means none
+ * ```
+ *
+ * Unknown sections are rejected rather than skipped, so a newer file is never
+ * silently misread. The leading zero byte means a text program can never be
+ * mistaken for a binary one.
+ */
+
+use super::symbols::{Symbol, Symbols};
+use super::{IntCodeMachine, Word};
+
+use std::convert::TryFrom;
+
+pub const MAGIC: &[u8; 4] = b"\0ICB";
+pub const VERSION: u8 = 1;
+
+const SECTION_PC: u8 = 1;
+const SECTION_RB: u8 = 2;
+const SECTION_SYMBOLS: u8 = 3;
+
+/** A machine together with the symbol names stored alongside it */
+#[derive(Clone)]
+pub struct Image<W = i64> {
+    pub machine: IntCodeMachine<W>,
+    pub symbols: Symbols,
+}
+
+fn write_varint(out: &mut Vec<u8>, mut v: u64) {
+    while v >= 0x80 {
+        out.push((v as u8) | 0x80);
+        v >>= 7;
+    }
+    out.push(v as u8);
+}
+
+fn zigzag(v: i64) -> u64 {
+    ((v << 1) ^ (v >> 63)) as u64
+}
+
+fn unzigzag(v: u64) -> i64 {
+    ((v >> 1) as i64) ^ -((v & 1) as i64)
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn byte(&mut self) -> Result<u8, String> {
+        let b = *self
+            .bytes
+            .get(self.pos)
+            .ok_or_else(|| format!("Unexpected end of file at byte {}", self.pos))?;
+        self.pos += 1;
+        Ok(b)
+    }
+
+    fn varint(&mut self) -> Result<u64, String> {
+        let start = self.pos;
+        let mut v = 0u64;
+        for shift in (0..64).step_by(7) {
+            let b = self.byte()?;
+            // The tenth byte only has room for the top bit of a u64
+            if shift == 63 && b & 0x7e != 0 {
+                return Err(format!("Varint overflows 64 bits at byte {}", start));
+            }
+            v |= ((b & 0x7f) as u64) << shift;
+            if b & 0x80 == 0 {
+                return Ok(v);
+            }
+        }
+        Err(format!("Varint too long at byte {}", start))
+    }
+
+    fn usize(&mut self) -> Result<usize, String> {
+        let v = self.varint()?;
+        usize::try_from(v).map_err(|_| format!("Value {} out of range before byte {}", v, self.pos))
+    }
+
+    /** A length prefixed string, or None if it is empty */
+    fn string(&mut self) -> Result<Option<String>, String> {
+        let len = self.usize()?;
+        if self.bytes.len() - self.pos < len {
+            return Err(format!("Unexpected end of file at byte {}", self.bytes.len()));
+        }
+        let s = std::str::from_utf8(&self.bytes[self.pos..self.pos + len])
+            .map_err(|_| format!("String at byte {} isn't utf8", self.pos))?;
+        self.pos += len;
+        Ok(Some(s.to_string()).filter(|s| !s.is_empty()))
+    }
+}
+
+pub fn is_binary(bytes: &[u8]) -> bool {
+    bytes.starts_with(MAGIC)
+}
+
+/**
+ * Encode a machine, including its pc and relative base, and its symbols.
+ * Fails if a word doesn't fit in an i64.
+ */
+pub fn encode<W: Word>(machine: &IntCodeMachine<W>, symbols: &Symbols) -> Result<Vec<u8>, String> {
+    let mut out = Vec::with_capacity(MAGIC.len() + 1 + machine.ops.len() * 2);
+    out.extend_from_slice(MAGIC);
+    out.push(VERSION);
+
+    write_varint(&mut out, machine.ops.len() as u64);
+    for (addr, w) in machine.ops.iter().enumerate() {
+        let v = w.to_i64().ok_or_else(|| format!("Word {} at {} is too large to encode", w, addr))?;
+        write_varint(&mut out, zigzag(v));
+    }
+
+    if machine.pc != 0 {
+        out.push(SECTION_PC);
+        write_varint(&mut out, machine.pc as u64);
+    }
+    if machine.rb != 0 {
+        out.push(SECTION_RB);
+        write_varint(&mut out, machine.rb as u64);
+    }
+    if !symbols.is_empty() {
+        out.push(SECTION_SYMBOLS);
+        write_varint(&mut out, symbols.len() as u64);
+        for (addr, symbol) in symbols.iter() {
+            write_varint(&mut out, addr as u64);
+            out.push(symbol.code as u8);
+            for s in &[&symbol.name, &symbol.comment] {
+                let s = s.as_deref().unwrap_or("");
+                write_varint(&mut out, s.len() as u64);
+                out.extend_from_slice(s.as_bytes());
+            }
+        }
+    }
+
+    Ok(out)
+}
+
+pub fn decode<W: Word>(bytes: &[u8]) -> Result<Image<W>, String> {
+    if !is_binary(bytes) {
+        return Err("Not a binary intcode file".to_string());
+    }
+    let mut r = Reader {
+        bytes,
+        pos: MAGIC.len(),
+    };
+
+    let version = r.byte()?;
+    if version != VERSION {
+        return Err(format!("Unsupported binary intcode version {}", version));
+    }
+
+    let count = r.usize()?;
+    // Every word takes at least a byte, so don't trust the count for allocation
+    let mut ops = Vec::with_capacity(count.min(bytes.len()));
+    for _ in 0..count {
+        ops.push(W::from(unzigzag(r.varint()?)));
+    }
+
+    let mut machine = IntCodeMachine::new(ops);
+    let mut symbols = Symbols::new();
+    while r.pos < bytes.len() {
+        let at = r.pos;
+        match r.byte()? {
+            SECTION_PC => machine.pc = r.usize()?,
+            SECTION_RB => machine.rb = r.usize()?,
+            SECTION_SYMBOLS => {
+                for _ in 0..r.usize()? {
+                    let addr = r.usize()?;
+                    let code = r.byte()? & 1 != 0;
+                    let name = r.string()?;
+                    let comment = r.string()?;
+                    symbols.insert(addr, Symbol { name, comment, code });
+                }
+            }
+            tag => return Err(format!("Unknown section {} at byte {}", tag, at)),
+        }
+    }
+
+    Ok(Image { machine, symbols })
+}
+
+/** Load either format, choosing by the magic header */
+pub fn load<W: Word>(bytes: &[u8]) -> Result<Image<W>, String> {
+    if is_binary(bytes) {
+        return decode(bytes);
+    }
+
+    let text = std::str::from_utf8(bytes).map_err(|_| "Program is neither binary nor text".to_string())?;
+    Ok(Image {
+        machine: IntCodeMachine::try_from_text(text)?,
+        symbols: Symbols::new(),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use num::BigInt;
+
+    #[test]
+    fn varints() {
+        for v in &[0, 1, -1, 63, -64, 64, i64::MAX, i64::MIN] {
+            let mut out = Vec::new();
+            write_varint(&mut out, zigzag(*v));
+            let mut r = Reader { bytes: &out, pos: 0 };
+            assert_eq!(unzigzag(r.varint().unwrap()), *v);
+            assert_eq!(r.pos, out.len());
+        }
+
+        // Small magnitudes of either sign take a single byte
+        let mut out = Vec::new();
+        write_varint(&mut out, zigzag(-64));
+        assert_eq!(out.len(), 1);
+
+        let mut max = vec![0xff; 9];
+        max.push(0x01);
+        assert_eq!(Reader { bytes: &max, pos: 0 }.varint(), Ok(u64::MAX));
+
+        let mut overflow = vec![0xff; 9];
+        overflow.push(0x02);
+        assert!(Reader { bytes: &overflow, pos: 0 }.varint().unwrap_err().contains("overflows"));
+
+        let too_long = vec![0x80; 11];
+        assert!(Reader { bytes: &too_long, pos: 0 }.varint().unwrap_err().contains("too long"));
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut machine = IntCodeMachine::load_file("109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99".to_string());
+        machine.pc = 4;
+        machine.rb = 1;
+        let symbols = Symbols::parse("100: counter\npc 4: increment ; bump the counter").unwrap();
+
+        let bytes = encode(&machine, &symbols).unwrap();
+        let image = load::<i64>(&bytes).unwrap();
+        assert_eq!(image.machine.memory(), machine.memory());
+        assert_eq!((image.machine.pc(), image.machine.rb()), (4, 1));
+        assert_eq!(image.symbols, symbols);
+
+        // Text still loads through the same entry point
+        let image = load::<BigInt>(b"1,2,-3").unwrap();
+        assert_eq!(image.machine.memory(), &[BigInt::from(1), BigInt::from(2), BigInt::from(-3)]);
+    }
+
+    #[test]
+    fn errors() {
+        let bytes = encode(&IntCodeMachine::load_file("1,2,3".to_string()), &Symbols::new()).unwrap();
+        assert!(decode::<i64>(&bytes[..bytes.len() - 1]).err().unwrap().contains("end of file"));
+
+        let mut newer = bytes.clone();
+        newer[MAGIC.len()] = 2;
+        assert!(decode::<i64>(&newer).err().unwrap().contains("version 2"));
+
+        let mut extra = bytes;
+        extra.push(42);
+        assert!(decode::<i64>(&extra).err().unwrap().contains("Unknown section 42"));
+
+        let big = IntCodeMachine::<BigInt>::from_text("1,100000000000000000000");
+        assert!(encode(&big, &Symbols::new()).is_err());
+    }
+}
@@ -0,0 +1,218 @@
+/*!
+ * Declarative intcode conformance tests.
+ *
+ * A suite is a text file of test cases, each introduced by a `[name]` header
+ * and followed by `key: value` lines:
+ *
+ * ```text
+ * # Comments start with a hash
+ * [day 2 example]
+ * program: 1,9,10,3,2,3,11,0,99,30,40,50
+ * memory: 3500,9,10,70,2,3,11,0,99,30,40,50
+ *
+ * [day 5 equal to 8]
+ * program: 3,9,8,9,10,9,4,9,99,-1,8
+ * input: 8
+ * output: 1
+ * ```
+ *
+ * `program` is required. `input` and `output` default to empty, so a case with no
+ * `output` line expects the program to produce nothing. `memory` is optional and
+ * is compared against the start of memory once the program halts.
+ */
+
+use super::{IntCodeIO, IntCodeMachine, IntCodeRunner};
+
+use std::sync::mpsc::channel;
+
+/** Programs taking more steps than this are assumed to be stuck */
+const STEP_LIMIT: usize = 10_000_000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TestCase {
+    pub name: String,
+    pub line: usize,
+    pub program: String,
+    pub input: Vec<i64>,
+    pub output: Vec<i64>,
+    pub memory: Option<Vec<i64>>,
+}
+
+fn parse_words(s: &str, line: usize) -> Result<Vec<i64>, String> {
+    s.split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|w| !w.is_empty())
+        .map(|w| w.parse::<i64>().map_err(|_| format!("line {}: invalid number '{}'", line, w)))
+        .collect()
+}
+
+fn join(words: &[i64]) -> String {
+    words.iter().map(|w| w.to_string()).collect::<Vec<String>>().join(",")
+}
+
+/** Parse a suite file into its test cases */
+pub fn parse_suite(contents: &str) -> Result<Vec<TestCase>, String> {
+    let mut cases: Vec<TestCase> = Vec::new();
+
+    for (i, line) in contents.lines().enumerate() {
+        let n = i + 1;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        if line.starts_with('[') && line.ends_with(']') {
+            cases.push(TestCase {
+                name: line[1..line.len() - 1].trim().to_string(),
+                line: n,
+                program: String::new(),
+                input: Vec::new(),
+                output: Vec::new(),
+                memory: None,
+            });
+            continue;
+        }
+
+        let case = cases
+            .last_mut()
+            .ok_or_else(|| format!("line {}: expected a [name] header before '{}'", n, line))?;
+        let (key, value) = match line.find(':') {
+            Some(p) => (line[..p].trim(), line[p + 1..].trim()),
+            None => return Err(format!("line {}: expected 'key: value', got '{}'", n, line)),
+        };
+
+        match key {
+            "program" => {
+                // Validate now so errors point at the suite rather than the run
+                parse_words(value, n)?;
+                case.program = value.to_string();
+            }
+            "input" => case.input = parse_words(value, n)?,
+            "output" => case.output = parse_words(value, n)?,
+            "memory" => case.memory = Some(parse_words(value, n)?),
+            _ => return Err(format!("line {}: unknown key '{}'", n, key)),
+        }
+    }
+
+    for case in &cases {
+        if case.program.is_empty() {
+            return Err(format!("line {}: test '{}' has no program", case.line, case.name));
+        }
+    }
+
+    Ok(cases)
+}
+
+fn diff_words(what: &str, expected: &[i64], actual: &[i64]) -> String {
+    let at = expected
+        .iter()
+        .zip(actual)
+        .position(|(e, a)| e != a)
+        .unwrap_or_else(|| expected.len().min(actual.len()));
+    format!(
+        "{} differs at index {}\n  expected: {}\n  actual:   {}",
+        what,
+        at,
+        join(expected),
+        join(actual)
+    )
+}
+
+impl TestCase {
+    /** Run the case against `common::intcode`, returning a diff on failure */
+    pub fn run(&self) -> Result<(), String> {
+        let (itx, irx) = channel::<i64>();
+        for v in &self.input {
+            itx.send(*v).unwrap();
+        }
+
+        let machine = IntCodeMachine::<i64>::from_text(&self.program);
+        let mut runner = IntCodeRunner::new(machine, irx);
+        let mut output = Vec::new();
+
+        let mut steps = 0;
+        loop {
+            steps += 1;
+            if steps > STEP_LIMIT {
+                return Err(format!("no halt after {} steps", STEP_LIMIT));
+            }
+
+            match runner.try_step() {
+                Err(e) => return Err(e.to_string()),
+                Ok(None) => {}
+                Ok(Some(IntCodeIO::Output(v))) => output.push(v),
+                Ok(Some(IntCodeIO::Input)) => {
+                    return Err(format!(
+                        "program wanted more than {} inputs (output so far: {})",
+                        self.input.len(),
+                        join(&output)
+                    ));
+                }
+                Ok(Some(IntCodeIO::Finished)) => break,
+            }
+        }
+
+        if output != self.output {
+            return Err(diff_words("output", &self.output, &output));
+        }
+
+        if let Some(memory) = &self.memory {
+            let actual: Vec<i64> = (0..memory.len()).map(|a| runner.machine.load(a)).collect();
+            if &actual != memory {
+                return Err(diff_words("memory", memory, &actual));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/** Run every case, returning the names and diffs of those which failed */
+pub fn run_suite(cases: &[TestCase]) -> Vec<(String, String)> {
+    cases
+        .iter()
+        .filter_map(|c| c.run().err().map(|e| (c.name.clone(), e)))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn corpus() {
+        let cases = parse_suite(include_str!("../../conformance/intcode.txt")).unwrap();
+        assert!(cases.len() > 20);
+        assert_eq!(run_suite(&cases), vec![]);
+    }
+
+    #[test]
+    fn failures() {
+        let cases = parse_suite(
+            "[wrong output]
+             program: 104,5,99
+             output: 6
+
+             [wrong memory]
+             program: 1101,1,1,0,99
+             memory: 3
+
+             [needs input]
+             program: 3,0,99",
+        )
+        .unwrap();
+
+        let failures = run_suite(&cases);
+        assert_eq!(failures.len(), 3);
+        assert_eq!(failures[0].1, "output differs at index 0\n  expected: 6\n  actual:   5");
+        assert_eq!(failures[1].1, "memory differs at index 0\n  expected: 3\n  actual:   2");
+        assert!(failures[2].1.starts_with("program wanted more than 0 inputs"));
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!(parse_suite("program: 99").unwrap_err().contains("line 1"));
+        assert!(parse_suite("[a]\nprogram: 1,x").unwrap_err().contains("invalid number 'x'"));
+        assert!(parse_suite("[a]\nfoo: 1").unwrap_err().contains("unknown key"));
+        assert!(parse_suite("[a]\ninput: 1").unwrap_err().contains("has no program"));
+    }
+}
@@ -0,0 +1,144 @@
+use super::disasm::{data_at, disassemble_at, Line};
+use super::{IntCodeMachine, Word};
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+/**
+ * Counts how many times each address was executed as the start of an instruction.
+ * Coverage from several runs of the same program can be merged together.
+ * Only executed addresses are stored, so a jump far past the program is cheap.
+ */
+#[derive(Clone, Default, Debug, PartialEq)]
+pub struct Coverage {
+    hits: BTreeMap<usize, u64>,
+}
+
+impl Coverage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&mut self, pc: usize) {
+        *self.hits.entry(pc).or_insert(0) += 1;
+    }
+
+    pub fn hits(&self, addr: usize) -> u64 {
+        self.hits.get(&addr).cloned().unwrap_or(0)
+    }
+
+    pub fn merge(&mut self, other: &Coverage) {
+        for (addr, o) in &other.hits {
+            *self.hits.entry(*addr).or_insert(0) += o;
+        }
+    }
+
+    /**
+     * Disassembly of `program` annotated with hit counts.
+     * Decoding follows executed addresses where they disagree with a linear sweep,
+     * so code jumped into part way through an instruction is still listed.
+     */
+    pub fn listing<W: Word>(&self, program: &IntCodeMachine<W>) -> Vec<(u64, Line<W>)> {
+        let mut lines = Vec::new();
+        let mut addr = 0;
+        while addr < program.memory().len() {
+            let mut line = disassemble_at(program, addr);
+            let end = addr + line.raw.len();
+            let overlaps_hit = (addr + 1..end).any(|a| self.hits(a) > 0);
+            if self.hits(addr) == 0 && (overlaps_hit || end > program.memory().len()) {
+                line = data_at(program, addr);
+            }
+
+            addr += line.raw.len();
+            lines.push((self.hits(line.addr), line));
+        }
+
+        lines
+    }
+
+    /** Returns (instructions executed, instructions decoded) for `program` */
+    pub fn summary<W: Word>(&self, program: &IntCodeMachine<W>) -> (usize, usize) {
+        let listing = self.listing(program);
+        let code = listing.iter().filter(|(_, l)| !l.data);
+        let total = code.clone().count();
+        let executed = code.filter(|(h, _)| *h > 0).count();
+        (executed, total)
+    }
+
+    /** Human readable coverage summary followed by the annotated listing */
+    pub fn report<W: Word>(&self, program: &IntCodeMachine<W>) -> String {
+        let (executed, total) = self.summary(program);
+        let percent = if total == 0 {
+            0.0
+        } else {
+            100.0 * executed as f64 / total as f64
+        };
+
+        let mut out = String::new();
+        writeln!(out, "Coverage: {}/{} instructions ({:.1}%)", executed, total, percent).unwrap();
+        writeln!(out).unwrap();
+        for (hits, line) in self.listing(program) {
+            let count = if hits > 0 {
+                hits.to_string()
+            } else if line.data {
+                String::new()
+            } else {
+                "#####".to_string()
+            };
+            writeln!(out, "{:>10} {}", count, line).unwrap();
+        }
+
+        out
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::intcode::{IntCodeIO, IntCodeRunner};
+    use std::sync::mpsc::channel;
+
+    fn run_with_input(program: &IntCodeMachine, input: i64) -> Coverage {
+        let (itx, irx) = channel();
+        itx.send(input).unwrap();
+        let mut runner = IntCodeRunner::new(program.clone(), irx);
+        runner.coverage = Some(Coverage::new());
+        while let IntCodeIO::Output(_) = runner.next() {}
+        runner.coverage.unwrap()
+    }
+
+    #[test]
+    fn branches() {
+        // Outputs 1 if the input is 8, otherwise 0
+        let program = IntCodeMachine::load_file("3,9,8,9,10,9,4,9,99,-1,8".to_string());
+        let cov = run_with_input(&program, 8);
+        assert_eq!(cov.summary(&program), (4, 4));
+        assert_eq!(cov.hits(0), 1);
+        assert_eq!(cov.hits(1), 0);
+
+        // Jumps over the "output 1" path when the input is zero
+        let program = IntCodeMachine::load_file("3,12,6,12,15,1,13,14,13,4,13,99,-1,0,1,9".to_string());
+        let zero = run_with_input(&program, 0);
+        assert_eq!(zero.summary(&program), (4, 5));
+        assert_eq!(zero.hits(5), 0);
+
+        let mut both = run_with_input(&program, 5);
+        both.merge(&zero);
+        assert_eq!(both.summary(&program), (5, 5));
+        assert_eq!(both.hits(0), 2);
+        assert_eq!(both.hits(5), 1);
+
+        let report = both.report(&program);
+        assert!(report.starts_with("Coverage: 5/5 instructions (100.0%)"));
+    }
+
+    #[test]
+    fn far_jump() {
+        // Jumps to an address far past the end of memory, which halts
+        let program = IntCodeMachine::load_file("1105,1,1000000000000".to_string());
+        let cov = run_with_input(&program, 0);
+        assert_eq!(cov.hits(0), 1);
+        assert_eq!(cov.hits(1000000000000), 1);
+        assert_eq!(cov.summary(&program), (1, 1));
+    }
+}
@@ -0,0 +1,506 @@
+/*!
+ * Debug Adapter Protocol server for intcode programs.
+ *
+ * `launch` takes `program` (path to a text or binary program) and optionally
+ * `ascii` (treat IO as text) and `stopOnEntry`. Source breakpoints are read as
+ * addresses, so line 25 means address 25; instruction breakpoints take the
+ * address as their `instructionReference`.
+ *
+ * Symbols from the program's sidecar file name the stack frame, and may be used
+ * in place of addresses in instruction breakpoints and expressions.
+ *
+ * Input is queued with the `evaluate` request: `input 1,2,3`, or any text in
+ * ascii mode. Any other expression is read as an address and shows that word.
+ */
+
+use super::binary;
+use super::disasm::disassemble_at_with;
+use super::symbols::Symbols;
+use super::{IntCodeIO, IntCodeRunner};
+
+use serde_json::{json, Value};
+
+use std::collections::HashSet;
+use std::io::{self, BufRead, Write};
+use std::sync::mpsc::{channel, Receiver, Sender};
+
+/** Instructions run between checks for incoming requests while the program is running */
+const RUN_CHUNK: usize = 10_000;
+
+/** Words in each expandable page of the memory view */
+const PAGE_SIZE: usize = 100;
+
+const REGISTERS_REF: usize = 1;
+const MEMORY_REF: usize = 2;
+const PAGE_REF_BASE: usize = 1000;
+
+/** Read one `Content-Length` framed message. Returns None at end of input */
+pub fn read_message<R: BufRead>(input: &mut R) -> io::Result<Option<Value>> {
+    let mut length = None;
+    loop {
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+
+        let line = line.trim();
+        if line.is_empty() {
+            break;
+        }
+        if let Some(l) = line.strip_prefix("Content-Length:") {
+            length = l.trim().parse::<usize>().ok();
+        }
+    }
+
+    let length = length.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Missing Content-Length"))?;
+    let mut body = vec![0; length];
+    input.read_exact(&mut body)?;
+    let value = serde_json::from_slice(&body).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    Ok(Some(value))
+}
+
+pub fn write_message<W: Write>(output: &mut W, message: &Value) -> io::Result<()> {
+    let body = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    output.flush()
+}
+
+struct Session<W: Write> {
+    output: W,
+    seq: i64,
+    runner: Option<IntCodeRunner>,
+    itx: Option<Sender<i64>>,
+    symbols: Symbols,
+    ascii: bool,
+    stop_on_entry: bool,
+    line_breakpoints: HashSet<usize>,
+    instruction_breakpoints: HashSet<usize>,
+    running: bool,
+    done: bool,
+}
+
+impl<W: Write> Session<W> {
+    fn new(output: W) -> Self {
+        Self {
+            output,
+            seq: 0,
+            runner: None,
+            itx: None,
+            symbols: Symbols::new(),
+            ascii: false,
+            stop_on_entry: false,
+            line_breakpoints: HashSet::new(),
+            instruction_breakpoints: HashSet::new(),
+            running: false,
+            done: false,
+        }
+    }
+
+    fn send(&mut self, mut message: Value) {
+        self.seq += 1;
+        message["seq"] = json!(self.seq);
+        write_message(&mut self.output, &message).expect("Failed writing to client");
+    }
+
+    fn event(&mut self, event: &str, body: Value) {
+        self.send(json!({ "type": "event", "event": event, "body": body }));
+    }
+
+    fn respond(&mut self, request: &Value, result: Result<Value, String>) {
+        let mut response = json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "command": request["command"],
+            "success": result.is_ok(),
+        });
+        match result {
+            Ok(body) => response["body"] = body,
+            Err(message) => response["message"] = json!(message),
+        }
+        self.send(response);
+    }
+
+    fn stopped(&mut self, reason: &str, description: &str) {
+        self.running = false;
+        self.event(
+            "stopped",
+            json!({ "reason": reason, "description": description, "threadId": 1, "allThreadsStopped": true }),
+        );
+    }
+
+    fn exited(&mut self, code: i64) {
+        self.running = false;
+        self.runner = None;
+        self.itx = None;
+        self.event("exited", json!({ "exitCode": code }));
+        self.event("terminated", json!({}));
+    }
+
+    fn output(&mut self, category: &str, text: String) {
+        self.event("output", json!({ "category": category, "output": text }));
+    }
+
+    fn runner(&self) -> Result<&IntCodeRunner, String> {
+        self.runner.as_ref().ok_or_else(|| "No program is running".to_string())
+    }
+
+    fn is_breakpoint(&self, addr: usize) -> bool {
+        self.line_breakpoints.contains(&addr) || self.instruction_breakpoints.contains(&addr)
+    }
+
+    /** Run up to `limit` instructions, reporting any reason to stop to the client */
+    fn execute(&mut self, limit: usize, stop_reason: Option<&str>) {
+        for i in 0..limit {
+            let pc = match &self.runner {
+                Some(runner) => runner.machine.pc(),
+                None => return,
+            };
+            if i > 0 && self.is_breakpoint(pc) {
+                self.stopped("breakpoint", &format!("Breakpoint at {}", pc));
+                return;
+            }
+
+            let result = self.runner.as_mut().unwrap().try_step();
+            match result {
+                Ok(None) => {}
+                Ok(Some(IntCodeIO::Output(v))) => {
+                    let text = if self.ascii && (0..128).contains(&v) {
+                        (v as u8 as char).to_string()
+                    } else {
+                        format!("{}\n", v)
+                    };
+                    self.output("stdout", text);
+                }
+                Ok(Some(IntCodeIO::Input)) => {
+                    self.stopped("pause", "Waiting for input");
+                    return;
+                }
+                Ok(Some(IntCodeIO::Finished)) => {
+                    self.exited(0);
+                    return;
+                }
+                Err(e) => {
+                    self.output("stderr", format!("{}\n", e));
+                    self.exited(1);
+                    return;
+                }
+            }
+        }
+
+        if let Some(reason) = stop_reason {
+            self.stopped(reason, "");
+        }
+    }
+
+    fn launch(&mut self, args: &Value) -> Result<Value, String> {
+        let path = args["program"].as_str().ok_or("launch requires a 'program' path")?;
+        let contents = std::fs::read(path).map_err(|e| format!("Couldn't read {}: {}", path, e))?;
+        let image = binary::load(&contents)?;
+        let machine = image.machine;
+        self.symbols = image.symbols;
+        for (addr, symbol) in Symbols::load_sidecar(path)?.iter() {
+            self.symbols.insert(addr, symbol.clone());
+        }
+
+        let (itx, irx) = channel::<i64>();
+        self.runner = Some(IntCodeRunner::new(machine, irx));
+        self.itx = Some(itx);
+        self.ascii = args["ascii"].as_bool().unwrap_or(false);
+        self.stop_on_entry = args["stopOnEntry"].as_bool().unwrap_or(false);
+        Ok(json!({}))
+    }
+
+    fn variables(&self, reference: usize) -> Result<Value, String> {
+        let runner = self.runner()?;
+        let m = &runner.machine;
+        let variables = match reference {
+            REGISTERS_REF => vec![
+                json!({ "name": "pc", "value": m.pc().to_string(), "variablesReference": 0 }),
+                json!({ "name": "rb", "value": m.rb().to_string(), "variablesReference": 0 }),
+                json!({
+                    "name": "awaiting_input",
+                    "value": runner.expects_input().to_string(),
+                    "variablesReference": 0
+                }),
+            ],
+            MEMORY_REF => (0..m.memory().len().div_ceil(PAGE_SIZE))
+                .map(|page| {
+                    json!({
+                        "name": format!("{}..{}", page * PAGE_SIZE, (page + 1) * PAGE_SIZE - 1),
+                        "value": "",
+                        "variablesReference": PAGE_REF_BASE + page
+                    })
+                })
+                .collect(),
+            r if r >= PAGE_REF_BASE => {
+                let start = (r - PAGE_REF_BASE) * PAGE_SIZE;
+                (start..start + PAGE_SIZE)
+                    .map(|a| json!({ "name": a.to_string(), "value": m.load(a).to_string(), "variablesReference": 0 }))
+                    .collect()
+            }
+            _ => return Err(format!("Unknown variables reference {}", reference)),
+        };
+        Ok(json!({ "variables": variables }))
+    }
+
+    fn evaluate(&mut self, expression: &str) -> Result<Value, String> {
+        let expression = expression.trim();
+        if let Some(text) = expression.strip_prefix("input") {
+            let itx = self.itx.as_ref().ok_or("No program is running")?;
+            let text = text.trim_start();
+            let mut count = 0;
+            if self.ascii {
+                for c in text.chars().chain(std::iter::once('\n')) {
+                    itx.send(c as i64).map_err(|_| "Program has exited".to_string())?;
+                    count += 1;
+                }
+            } else {
+                for s in text.split(|c: char| c == ',' || c.is_whitespace()).filter(|s| !s.is_empty()) {
+                    let v = s.parse::<i64>().map_err(|_| format!("Invalid number '{}'", s))?;
+                    itx.send(v).map_err(|_| "Program has exited".to_string())?;
+                    count += 1;
+                }
+            }
+            return Ok(json!({ "result": format!("Queued {} input values", count), "variablesReference": 0 }));
+        }
+
+        let addr = self
+            .symbols
+            .resolve(expression.trim_start_matches('[').trim_end_matches(']'))
+            .ok_or_else(|| format!("Expected 'input ...' or an address, got '{}'", expression))?;
+        let m = &self.runner()?.machine;
+        Ok(json!({ "result": m.load(addr).to_string(), "variablesReference": 0 }))
+    }
+
+    fn handle(&mut self, request: &Value) {
+        let args = &request["arguments"];
+        let command = request["command"].as_str().unwrap_or("");
+        let result = match command {
+            "initialize" => Ok(json!({
+                "supportsConfigurationDoneRequest": true,
+                "supportsInstructionBreakpoints": true,
+                "supportsTerminateRequest": true,
+            })),
+            "launch" => self.launch(args),
+            "setBreakpoints" => {
+                let lines: Vec<usize> = args["breakpoints"]
+                    .as_array()
+                    .map(|b| b.iter().filter_map(|b| b["line"].as_u64()).map(|l| l as usize).collect())
+                    .unwrap_or_default();
+                self.line_breakpoints = lines.iter().cloned().collect();
+                let breakpoints: Vec<Value> = lines.iter().map(|l| json!({ "verified": true, "line": l })).collect();
+                Ok(json!({ "breakpoints": breakpoints }))
+            }
+            "setInstructionBreakpoints" => {
+                let mut breakpoints = Vec::new();
+                self.instruction_breakpoints.clear();
+                for b in args["breakpoints"].as_array().cloned().unwrap_or_default() {
+                    let addr = b["instructionReference"].as_str().and_then(|r| self.symbols.resolve(r));
+                    let addr = addr.map(|a| a as i64 + b["offset"].as_i64().unwrap_or(0));
+                    match addr {
+                        Some(a) if a >= 0 => {
+                            self.instruction_breakpoints.insert(a as usize);
+                            breakpoints.push(json!({ "verified": true, "instructionReference": a.to_string() }));
+                        }
+                        _ => breakpoints.push(json!({ "verified": false, "message": "Invalid address" })),
+                    }
+                }
+                Ok(json!({ "breakpoints": breakpoints }))
+            }
+            "configurationDone" => Ok(json!({})),
+            "threads" => Ok(json!({ "threads": [{ "id": 1, "name": "intcode" }] })),
+            "stackTrace" => self.runner().map(|r| {
+                let pc = r.machine.pc();
+                json!({
+                    "stackFrames": [{
+                        "id": 1,
+                        "name": disassemble_at_with(&r.machine, pc, &self.symbols).text,
+                        "line": pc,
+                        "column": 0,
+                        "instructionPointerReference": pc.to_string(),
+                    }],
+                    "totalFrames": 1
+                })
+            }),
+            "scopes" => Ok(json!({
+                "scopes": [
+                    { "name": "Registers", "variablesReference": REGISTERS_REF, "expensive": false },
+                    { "name": "Memory", "variablesReference": MEMORY_REF, "expensive": false },
+                ]
+            })),
+            "variables" => self.variables(args["variablesReference"].as_u64().unwrap_or(0) as usize),
+            "evaluate" => self.evaluate(args["expression"].as_str().unwrap_or("")),
+            "continue" => self.runner().map(|_| json!({ "allThreadsContinued": true })),
+            "next" | "stepIn" | "stepOut" => self.runner().map(|_| json!({})),
+            "pause" => Ok(json!({})),
+            "disconnect" | "terminate" => Ok(json!({})),
+            _ => Err(format!("Unsupported request '{}'", command)),
+        };
+
+        let success = result.is_ok();
+        self.respond(request, result);
+        if !success {
+            return;
+        }
+
+        // Events which must follow the response
+        match command {
+            "initialize" => self.event("initialized", json!({})),
+            "configurationDone" if self.stop_on_entry => self.stopped("entry", ""),
+            "configurationDone" | "continue" => self.running = true,
+            "next" | "stepIn" | "stepOut" => self.execute(1, Some("step")),
+            "pause" if self.running => self.stopped("pause", ""),
+            "disconnect" | "terminate" => self.done = true,
+            _ => {}
+        }
+    }
+}
+
+/**
+ * Serve DAP requests from `input` until the client disconnects,
+ * writing responses and events to `output`.
+ */
+pub fn serve<R, W>(input: R, output: W) -> W
+where
+    R: BufRead + Send + 'static,
+    W: Write,
+{
+    // Read requests on another thread so a running program can be paused
+    let (rtx, rrx): (Sender<Value>, Receiver<Value>) = channel();
+    std::thread::spawn(move || {
+        let mut input = input;
+        while let Ok(Some(message)) = read_message(&mut input) {
+            if rtx.send(message).is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut session = Session::new(output);
+
+    while !session.done {
+        if session.running {
+            while let Ok(request) = rrx.try_recv() {
+                session.handle(&request);
+            }
+            if session.running {
+                session.execute(RUN_CHUNK, None);
+            }
+        } else {
+            match rrx.recv() {
+                Ok(request) => session.handle(&request),
+                Err(_) => break,
+            }
+        }
+    }
+
+    session.output
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Cursor;
+
+    fn request(seq: i64, command: &str, arguments: Value) -> Value {
+        json!({ "seq": seq, "type": "request", "command": command, "arguments": arguments })
+    }
+
+    fn messages(output: Vec<u8>) -> Vec<Value> {
+        let mut cursor = Cursor::new(output);
+        let mut messages = Vec::new();
+        while let Some(m) = read_message(&mut cursor).unwrap() {
+            messages.push(m);
+        }
+        messages
+    }
+
+    #[test]
+    fn framing() {
+        let mut input = Vec::new();
+        write_message(&mut input, &request(1, "initialize", json!({}))).unwrap();
+        write_message(&mut input, &request(2, "disconnect", json!({}))).unwrap();
+
+        let out = messages(serve(Cursor::new(input), Vec::new()));
+        assert_eq!(out.len(), 3);
+        assert_eq!(out[0]["command"], "initialize");
+        assert_eq!(out[0]["body"]["supportsInstructionBreakpoints"], true);
+        assert_eq!(out[1]["event"], "initialized");
+        assert_eq!(out[2]["command"], "disconnect");
+        assert_eq!(out.iter().map(|m| m["seq"].as_i64().unwrap()).collect::<Vec<_>>(), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn session() {
+        let program = std::env::temp_dir().join(format!("dap_test_{}.txt", std::process::id()));
+        // Reads a value, doubles it, outputs it
+        std::fs::write(&program, "3,9,1002,9,2,9,4,9,99,0").unwrap();
+
+        let requests = vec![
+            request(1, "launch", json!({ "program": program.to_str().unwrap(), "stopOnEntry": true })),
+            request(2, "setInstructionBreakpoints", json!({ "breakpoints": [{ "instructionReference": "6" }] })),
+            request(3, "configurationDone", json!({})),
+            request(4, "evaluate", json!({ "expression": "input 21" })),
+            request(5, "next", json!({ "threadId": 1 })),
+            request(6, "variables", json!({ "variablesReference": REGISTERS_REF })),
+            request(7, "continue", json!({ "threadId": 1 })),
+            request(8, "evaluate", json!({ "expression": "9" })),
+            request(9, "continue", json!({ "threadId": 1 })),
+        ];
+
+        // Run to the next stop after each request, as a client waiting on events would
+        let mut session = Session::new(Vec::new());
+        for r in &requests {
+            session.handle(r);
+            while session.running {
+                session.execute(RUN_CHUNK, None);
+            }
+        }
+        std::fs::remove_file(&program).unwrap();
+        let messages = messages(session.output);
+
+        let events: Vec<&str> = messages.iter().filter_map(|m| m["event"].as_str()).collect();
+        assert_eq!(events, vec!["stopped", "stopped", "stopped", "output", "exited", "terminated"]);
+
+        let stops: Vec<&str> = messages
+            .iter()
+            .filter(|m| m["event"] == "stopped")
+            .map(|m| m["body"]["reason"].as_str().unwrap())
+            .collect();
+        assert_eq!(stops, vec!["entry", "step", "breakpoint"]);
+
+        let response = |seq: i64| messages.iter().find(|m| m["request_seq"] == seq).unwrap();
+        assert_eq!(response(6)["body"]["variables"][0]["value"], "2");
+        assert_eq!(response(8)["body"]["result"], "42");
+
+        let output = messages.iter().find(|m| m["event"] == "output").unwrap();
+        assert_eq!(output["body"]["output"], "42\n");
+    }
+
+    #[test]
+    fn input_after_exit() {
+        let program = std::env::temp_dir().join(format!("dap_exit_test_{}.txt", std::process::id()));
+        std::fs::write(&program, "99").unwrap();
+
+        let requests = vec![
+            request(1, "launch", json!({ "program": program.to_str().unwrap() })),
+            request(2, "configurationDone", json!({})),
+            request(3, "evaluate", json!({ "expression": "input 1" })),
+        ];
+
+        let mut session = Session::new(Vec::new());
+        for r in &requests {
+            session.handle(r);
+            while session.running {
+                session.execute(RUN_CHUNK, None);
+            }
+        }
+        std::fs::remove_file(&program).unwrap();
+        let messages = messages(session.output);
+
+        assert!(messages.iter().any(|m| m["event"] == "exited"));
+        let response = messages.iter().find(|m| m["request_seq"] == 3).unwrap();
+        assert_eq!(response["success"], false);
+        assert_eq!(response["message"], "No program is running");
+    }
+}
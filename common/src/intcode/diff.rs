@@ -0,0 +1,292 @@
+use super::disasm::{disassemble, Line};
+use super::{IntCodeMachine, Word};
+
+use std::fmt;
+
+/**
+ * One entry of the difference between two programs.
+ * Instructions are aligned by their opcode word, so an instruction whose
+ * operands differ is reported as changed rather than as removed and added,
+ * though identical instructions are preferred when there is a choice.
+ */
+pub enum Diff<W = i64> {
+    /** Identical words at `a` and `b`, which differ if the code has moved */
+    Same { a: usize, b: usize, len: usize },
+    /** The same instruction with different operands */
+    Changed { a: Line<W>, b: Line<W> },
+    /** A run of data words which differ */
+    Data { a: usize, b: usize, old: Vec<W>, new: Vec<W> },
+    Removed(Line<W>),
+    Added(Line<W>),
+}
+
+/**
+ * How well two lines align: 2 if they are identical, 1 if they are the same
+ * instruction with different operands or both data, otherwise 0
+ */
+fn score<W: Word>(x: &Line<W>, y: &Line<W>) -> u32 {
+    if x.raw == y.raw && x.data == y.data {
+        2
+    } else if x.data && y.data || !x.data && !y.data && x.raw[0] == y.raw[0] {
+        1
+    } else {
+        0
+    }
+}
+
+/**
+ * The most table cells the alignment will fill in, at 4 bytes each. Beyond
+ * this it falls back to a greedy alignment.
+ */
+const MAX_TABLE_CELLS: usize = 1 << 22;
+
+/** How far the greedy alignment looks ahead for a line to resynchronise on */
+const GREEDY_WINDOW: usize = 64;
+
+/** Pairs of aligned line indexes, found by the highest scoring common subsequence */
+fn align<W: Word>(a: &[Line<W>], b: &[Line<W>]) -> Vec<(usize, usize)> {
+    align_within(a, b, MAX_TABLE_CELLS)
+}
+
+fn align_within<W: Word>(a: &[Line<W>], b: &[Line<W>], max_cells: usize) -> Vec<(usize, usize)> {
+    let same = |x: &Line<W>, y: &Line<W>| score(x, y) == 2;
+    let prefix = a.iter().zip(b).take_while(|(x, y)| same(x, y)).count();
+    let suffix = a[prefix..]
+        .iter()
+        .rev()
+        .zip(b[prefix..].iter().rev())
+        .take_while(|(x, y)| same(x, y))
+        .count();
+    let (a, b) = (&a[prefix..a.len() - suffix], &b[prefix..b.len() - suffix]);
+    let (n, m) = (a.len(), b.len());
+
+    let middle = match (n + 1).checked_mul(m + 1) {
+        Some(cells) if cells <= max_cells => align_table(a, b),
+        _ => align_greedy(a, b),
+    };
+
+    let mut pairs: Vec<(usize, usize)> = (0..prefix).map(|i| (i, i)).collect();
+    pairs.extend(middle.into_iter().map(|(i, j)| (prefix + i, prefix + j)));
+    pairs.extend((0..suffix).map(|k| (prefix + n + k, prefix + m + k)));
+    pairs
+}
+
+/** The best alignment, from a table of (n + 1) * (m + 1) scores */
+fn align_table<W: Word>(a: &[Line<W>], b: &[Line<W>]) -> Vec<(usize, usize)> {
+    let (n, m) = (a.len(), b.len());
+
+    // best[at(i, j)] is the best score aligning a[i..] with b[j..]
+    let at = |i: usize, j: usize| i * (m + 1) + j;
+    let mut best = vec![0u32; (n + 1) * (m + 1)];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            let s = score(&a[i], &b[j]);
+            let matched = if s > 0 { s + best[at(i + 1, j + 1)] } else { 0 };
+            best[at(i, j)] = matched.max(best[at(i + 1, j)]).max(best[at(i, j + 1)]);
+        }
+    }
+
+    let mut pairs = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < n && j < m {
+        let s = score(&a[i], &b[j]);
+        if s > 0 && best[at(i, j)] == s + best[at(i + 1, j + 1)] {
+            pairs.push((i, j));
+            i += 1;
+            j += 1;
+        } else if best[at(i + 1, j)] >= best[at(i, j + 1)] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    pairs
+}
+
+/**
+ * A quick alignment for programs too different to tabulate: pair lines while
+ * they align, and otherwise skip ahead on whichever side reaches a line
+ * identical to the other's sooner
+ */
+fn align_greedy<W: Word>(a: &[Line<W>], b: &[Line<W>]) -> Vec<(usize, usize)> {
+    let same = |x: &Line<W>, y: &Line<W>| score(x, y) == 2;
+    let mut pairs = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        if score(&a[i], &b[j]) > 0 {
+            pairs.push((i, j));
+            i += 1;
+            j += 1;
+            continue;
+        }
+        let skip_a = a[i..].iter().take(GREEDY_WINDOW).position(|x| same(x, &b[j]));
+        let skip_b = b[j..].iter().take(GREEDY_WINDOW).position(|y| same(&a[i], y));
+        match (skip_a, skip_b) {
+            (Some(k), Some(l)) if k <= l => i += k,
+            (Some(k), None) => i += k,
+            (_, Some(l)) => j += l,
+            (None, None) => {
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+    pairs
+}
+
+/** Compare two programs instruction by instruction */
+pub fn diff<W: Word>(a: &IntCodeMachine<W>, b: &IntCodeMachine<W>) -> Vec<Diff<W>> {
+    let (a, b) = (disassemble(a), disassemble(b));
+
+    let mut diffs = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    for (pi, pj) in align(&a, &b).into_iter().chain(std::iter::once((a.len(), b.len()))) {
+        diffs.extend(a[i..pi].iter().cloned().map(Diff::Removed));
+        diffs.extend(b[j..pj].iter().cloned().map(Diff::Added));
+        if pi < a.len() {
+            push_pair(&mut diffs, a[pi].clone(), b[pj].clone());
+        }
+        i = pi + 1;
+        j = pj + 1;
+    }
+
+    diffs
+}
+
+/** Add an aligned pair, merging it into the previous entry where it continues a run */
+fn push_pair<W: Word>(diffs: &mut Vec<Diff<W>>, x: Line<W>, y: Line<W>) {
+    if x.raw == y.raw {
+        if let Some(Diff::Same { a, b, len }) = diffs.last_mut() {
+            if *a + *len == x.addr && *b + *len == y.addr {
+                *len += x.raw.len();
+                return;
+            }
+        }
+        diffs.push(Diff::Same {
+            a: x.addr,
+            b: y.addr,
+            len: x.raw.len(),
+        });
+    } else if x.data {
+        if let Some(Diff::Data { a, b, old, new }) = diffs.last_mut() {
+            if *a + old.len() == x.addr && *b + new.len() == y.addr {
+                old.extend(x.raw);
+                new.extend(y.raw);
+                return;
+            }
+        }
+        diffs.push(Diff::Data {
+            a: x.addr,
+            b: y.addr,
+            old: x.raw,
+            new: y.raw,
+        });
+    } else {
+        diffs.push(Diff::Changed { a: x, b: y });
+    }
+}
+
+fn join<W: Word>(words: &[W]) -> String {
+    words.iter().map(|w| w.to_string()).collect::<Vec<String>>().join(",")
+}
+
+impl<W: Word> fmt::Display for Diff<W> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Diff::Same { a, b, len } => {
+                write!(f, "= {:>6} {:>6}  {} words", a, b, len)?;
+                if a != b {
+                    write!(f, " shifted by {:+}", *b as i64 - *a as i64)?;
+                }
+                Ok(())
+            }
+            Diff::Changed { a, b } => write!(f, "~ {:>6} {:>6}  {}  =>  {}", a.addr, b.addr, a.text, b.text),
+            Diff::Data { a, b, old, new } => {
+                write!(f, "~ {:>6} {:>6}  DATA {}  =>  DATA {}", a, b, join(old), join(new))
+            }
+            Diff::Removed(line) => write!(f, "- {:>6} {:>6}  {}", line.addr, "", line.text),
+            Diff::Added(line) => write!(f, "+ {:>6} {:>6}  {}", "", line.addr, line.text),
+        }
+    }
+}
+
+/** Diff listing, leaving out unchanged code which hasn't moved, followed by a summary */
+pub fn report<W: Word>(diffs: &[Diff<W>]) -> String {
+    let mut out = String::new();
+    let (mut changed, mut data, mut removed, mut added, mut shifted) = (0, 0, 0, 0, 0);
+    for d in diffs {
+        match d {
+            Diff::Same { a, b, .. } if a == b => continue,
+            Diff::Same { .. } => shifted += 1,
+            Diff::Changed { .. } => changed += 1,
+            Diff::Data { old, .. } => data += old.len(),
+            Diff::Removed(_) => removed += 1,
+            Diff::Added(_) => added += 1,
+        }
+        out.push_str(&format!("{}\n", d));
+    }
+
+    out.push_str(&format!(
+        "{} instructions changed, {} data words changed, {} removed, {} added, {} shifted regions\n",
+        changed, data, removed, added, shifted
+    ));
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn diff_text(a: &str, b: &str) -> Vec<String> {
+        let a = IntCodeMachine::load_file(a.to_string());
+        let b = IntCodeMachine::load_file(b.to_string());
+        diff(&a, &b).iter().map(|d| d.to_string()).collect()
+    }
+
+    #[test]
+    fn changed_constant() {
+        let d = diff_text("1101,3,4,9,104,7,99,50,60,0", "1101,3,5,9,104,7,99,50,80,0");
+        assert_eq!(d, vec![
+            "~      0      0  ADD 3, 4, [9]  =>  ADD 3, 5, [9]",
+            "=      4      4  4 words",
+            "~      8      8  DATA 60  =>  DATA 80",
+            "=      9      9  1 words",
+        ]);
+    }
+
+    #[test]
+    fn inserted_code() {
+        // An extra output in the middle moves everything after it along
+        let d = diff_text("104,1,104,2,99,70", "104,1,104,9,104,2,99,70");
+        assert_eq!(d, vec![
+            "=      0      0  2 words",
+            "+             2  OUT 9",
+            "=      2      4  4 words shifted by +2",
+        ]);
+
+        let a = IntCodeMachine::load_file("104,1,104,2,99,70".to_string());
+        let b = IntCodeMachine::load_file("104,2,99,70".to_string());
+        let r = report(&diff(&a, &b));
+        assert!(r.starts_with("-      0         OUT 1\n"));
+        assert!(r.ends_with("0 instructions changed, 0 data words changed, 1 removed, 0 added, 1 shifted regions\n"));
+    }
+
+    #[test]
+    fn greedy_fallback() {
+        let lines = |text: &str| disassemble(&IntCodeMachine::load_file(text.to_string()));
+
+        // A changed operand, and an output added before a multiply
+        let a = lines("1101,3,4,20,1002,20,2,20,4,20,99,7");
+        let b = lines("1101,3,5,20,104,0,1002,20,2,20,4,20,99,7");
+        let table = align_within(&a, &b, usize::MAX);
+        assert_eq!(align_within(&a, &b, 0), table);
+        assert_eq!(table.len(), a.len());
+
+        // Unrelated programs still align only compatible lines, in order
+        let a = lines("104,1,104,2,99");
+        let b = lines("1101,1,2,9,1101,3,4,9,1");
+        let pairs = align_within(&a, &b, 0);
+        assert!(pairs.iter().all(|&(i, j)| score(&a[i], &b[j]) > 0));
+        assert!(pairs.windows(2).all(|w| w[0].0 < w[1].0 && w[0].1 < w[1].1));
+    }
+}
@@ -0,0 +1,161 @@
+use super::symbols::Symbols;
+use super::{IntCodeMachine, Word};
+
+/** A single line of disassembly: either a decoded instruction or a data word */
+#[derive(Clone)]
+pub struct Line<W = i64> {
+    pub addr: usize,
+    pub raw: Vec<W>,
+    pub text: String,
+    pub data: bool,
+    pub label: Option<String>,
+    pub comment: Option<String>,
+}
+
+/** Show the word at `addr` as data, regardless of whether it decodes */
+pub fn data_at<W: Word>(machine: &IntCodeMachine<W>, addr: usize) -> Line<W> {
+    let word = machine.load(addr);
+    Line {
+        addr,
+        text: format!("DATA {}", word),
+        raw: vec![word],
+        data: true,
+        label: None,
+        comment: None,
+    }
+}
+
+/**
+ * Disassemble the instruction at `addr`.
+ * Words which don't decode to a known instruction are shown as `DATA`.
+ */
+pub fn disassemble_at<W: Word>(machine: &IntCodeMachine<W>, addr: usize) -> Line<W> {
+    disassemble_at_with(machine, addr, &Symbols::new())
+}
+
+/** As `disassemble_at`, showing names, labels and comments from `symbols` */
+pub fn disassemble_at_with<W: Word>(machine: &IntCodeMachine<W>, addr: usize, symbols: &Symbols) -> Line<W> {
+    let opcode = machine.load(addr);
+    let decoded = if machine.opcodes().contains(&opcode) {
+        machine.decode(addr)
+    } else {
+        None
+    };
+
+    let line = match decoded {
+        Some((ins, len)) => Line {
+            addr,
+            raw: (addr..addr + len).map(|a| machine.load(a)).collect(),
+            text: ins.symbolic(symbols),
+            data: false,
+            label: None,
+            comment: None,
+        },
+        None => data_at(machine, addr),
+    };
+    annotate(line, symbols)
+}
+
+fn annotate<W>(mut line: Line<W>, symbols: &Symbols) -> Line<W> {
+    line.label = symbols.name(line.addr).map(|n| n.to_string());
+    line.comment = symbols.comment(line.addr).map(|c| c.to_string());
+    line
+}
+
+/** Linear sweep disassembly of the whole of memory */
+pub fn disassemble<W: Word>(machine: &IntCodeMachine<W>) -> Vec<Line<W>> {
+    disassemble_with(machine, &Symbols::new())
+}
+
+/**
+ * Linear sweep disassembly using `symbols`.
+ * Addresses marked as code always start an instruction, so the sweep
+ * resynchronises at labels which fall inside data.
+ */
+pub fn disassemble_with<W: Word>(machine: &IntCodeMachine<W>, symbols: &Symbols) -> Vec<Line<W>> {
+    let mut lines = Vec::new();
+    let mut addr = 0;
+    while addr < machine.memory().len() {
+        let mut line = disassemble_at_with(machine, addr, symbols);
+        if (addr + 1..addr + line.raw.len()).any(|a| symbols.label(a).is_some()) {
+            line = annotate(data_at(machine, addr), symbols);
+        }
+        addr += line.raw.len();
+        lines.push(line);
+    }
+
+    lines
+}
+
+impl<W: Word> std::fmt::Display for Line<W> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if let Some(label) = &self.label {
+            writeln!(f, "{}:", label)?;
+        }
+        let raw: Vec<String> = self.raw.iter().map(|v| v.to_string()).collect();
+        write!(f, "{:>6}: {:<28} {}", self.addr, raw.join(","), self.text)?;
+        if let Some(comment) = &self.comment {
+            write!(f, " ; {}", comment)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn disassemble_day9_quine() {
+        let m = IntCodeMachine::load_file("109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99".to_string());
+        let text: Vec<String> = disassemble(&m).into_iter().map(|l| l.text).collect();
+        assert_eq!(text, vec![
+            "ARB 1",
+            "OUT [rb-1]",
+            "ADD [100], 1, [100]",
+            "EQ  [100], 16, [101]",
+            "JZ  [101], 0",
+            "HLT",
+        ]);
+    }
+
+    #[test]
+    fn data_words() {
+        let m = IntCodeMachine::load_file("1002,4,3,4,33,12345".to_string());
+        let lines = disassemble(&m);
+        assert_eq!(lines[0].text, "MUL [4], 3, [4]");
+        assert_eq!(lines[1].text, "DATA 33");
+        assert_eq!(lines[2].text, "DATA 12345");
+    }
+
+    #[test]
+    fn symbols() {
+        let m = IntCodeMachine::load_file("109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99".to_string());
+        let symbols = Symbols::parse(
+            "pc 0: start
+             2: ; output the next word
+             100: counter
+             101: done",
+        )
+        .unwrap();
+
+        let lines = disassemble_with(&m, &symbols);
+        let text: Vec<&str> = lines.iter().map(|l| l.text.as_str()).collect();
+        assert_eq!(text, vec![
+            "ARB 1",
+            "OUT [rb-1]",
+            "ADD [counter], 1, [counter]",
+            "EQ  [counter], 16, [done]",
+            "JZ  [done], start",
+            "HLT",
+        ]);
+        assert_eq!(lines[0].to_string(), format!("start:\n{:>6}: {:<28} ARB 1", 0, "109,1"));
+        assert!(lines[1].to_string().ends_with("OUT [rb-1] ; output the next word"));
+
+        // A code label inside what looks like an instruction splits it
+        let m = IntCodeMachine::load_file("1,104,7,99".to_string());
+        let symbols = Symbols::parse("pc 1: out").unwrap();
+        let text: Vec<String> = disassemble_with(&m, &symbols).into_iter().map(|l| l.text).collect();
+        assert_eq!(text, vec!["DATA 1", "OUT 7", "HLT"]);
+    }
+}
@@ -0,0 +1,186 @@
+use std::collections::HashMap;
+
+/**
+ * Counts reads, writes and executes of every address during a run, and
+ * renders them as an image to show where a program keeps its code and data.
+ * Only addresses which were touched are stored.
+ */
+#[derive(Clone, Default, Debug, PartialEq)]
+pub struct Heatmap {
+    reads: HashMap<usize, u64>,
+    writes: HashMap<usize, u64>,
+    executes: HashMap<usize, u64>,
+}
+
+fn bump(counts: &mut HashMap<usize, u64>, addr: usize) {
+    *counts.entry(addr).or_insert(0) += 1;
+}
+
+fn get(counts: &HashMap<usize, u64>, addr: usize) -> u64 {
+    counts.get(&addr).cloned().unwrap_or(0)
+}
+
+fn end(counts: &HashMap<usize, u64>) -> usize {
+    counts.keys().max().map_or(0, |a| a + 1)
+}
+
+/** Log scale a count to a colour intensity, so rarely touched addresses still show */
+fn intensity(count: u64, max: u64) -> u8 {
+    if count == 0 {
+        0
+    } else {
+        // Touched addresses are always at least dimly visible
+        (48.0 + 207.0 * (count as f64).ln_1p() / (max as f64).ln_1p()) as u8
+    }
+}
+
+impl Heatmap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /** Record a read of `addr`. Jump targets are only read when the jump is taken */
+    pub fn record_read(&mut self, addr: usize) {
+        bump(&mut self.reads, addr);
+    }
+
+    pub fn record_write(&mut self, addr: usize) {
+        bump(&mut self.writes, addr);
+    }
+
+    /** Record execution of the instruction of `len` words at `pc` */
+    pub fn record_execute(&mut self, pc: usize, len: usize) {
+        for addr in pc..pc + len {
+            bump(&mut self.executes, addr);
+        }
+    }
+
+    pub fn reads(&self, addr: usize) -> u64 {
+        get(&self.reads, addr)
+    }
+
+    pub fn writes(&self, addr: usize) -> u64 {
+        get(&self.writes, addr)
+    }
+
+    pub fn executes(&self, addr: usize) -> u64 {
+        get(&self.executes, addr)
+    }
+
+    /** One past the highest address touched */
+    pub fn len(&self) -> usize {
+        end(&self.reads).max(end(&self.writes)).max(end(&self.executes))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /**
+     * Render addresses `0..len` as a binary PPM image with `width` addresses per
+     * row, each drawn as a `scale` pixel square. Writes are red, reads green and
+     * executes blue, each relative to the busiest address for that kind of access.
+     */
+    pub fn to_ppm(&self, len: usize, width: usize, scale: usize) -> Vec<u8> {
+        let rows = len.div_ceil(width).max(1);
+        let max = |counts: &HashMap<usize, u64>| counts.values().cloned().max().unwrap_or(0);
+        let (max_r, max_w, max_x) = (max(&self.reads), max(&self.writes), max(&self.executes));
+
+        let mut out = format!("P6\n{} {}\n255\n", width * scale, rows * scale).into_bytes();
+        for row in 0..rows {
+            let mut line = Vec::with_capacity(width * scale * 3);
+            for addr in row * width..(row + 1) * width {
+                let pixel = [
+                    intensity(self.writes(addr), max_w),
+                    intensity(self.reads(addr), max_r),
+                    intensity(self.executes(addr), max_x),
+                ];
+                for _ in 0..scale {
+                    line.extend_from_slice(&pixel);
+                }
+            }
+            for _ in 0..scale {
+                out.extend_from_slice(&line);
+            }
+        }
+
+        out
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::intcode::{IntCodeIO, IntCodeMachine, IntCodeRunner};
+    use std::sync::mpsc::channel;
+
+    #[test]
+    fn counts() {
+        // The day 9 quine: reads and writes its counter at 100 every loop
+        let program = "109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99";
+        let (_itx, irx) = channel();
+        let mut runner = IntCodeRunner::new(IntCodeMachine::load_file(program.to_string()), irx);
+        runner.heatmap = Some(Heatmap::new());
+        while let IntCodeIO::Output(_) = runner.next() {}
+        let heatmap = runner.heatmap.unwrap();
+
+        assert_eq!(heatmap.executes(0), 16);
+        assert_eq!(heatmap.executes(14), 16);
+        assert_eq!(heatmap.executes(15), 1);
+        assert_eq!(heatmap.writes(100), 16);
+        assert_eq!(heatmap.reads(100), 32);
+        assert_eq!(heatmap.reads(101), 16);
+        assert_eq!(heatmap.writes(0), 0);
+        // Each OUT [rb-1] reads the next word of the program
+        assert_eq!(heatmap.reads(0), 1);
+        assert_eq!(heatmap.reads(15), 1);
+        assert_eq!(heatmap.len(), 102);
+    }
+
+    fn run(program: &str) -> Heatmap {
+        let (_itx, irx) = channel();
+        let mut runner = IntCodeRunner::new(IntCodeMachine::load_file(program.to_string()), irx);
+        runner.heatmap = Some(Heatmap::new());
+        while let IntCodeIO::Output(_) = runner.next() {}
+        runner.heatmap.unwrap()
+    }
+
+    #[test]
+    fn jumps() {
+        // JNZ 0, [9] isn't taken, then JZ 0, [9] jumps to the HLT at 6
+        let heatmap = run("105,0,9,106,0,9,99,0,0,6");
+        assert_eq!(heatmap.reads(9), 1);
+        assert_eq!(heatmap.executes(6), 1);
+
+        // Halts on the zero word far past the end of memory
+        let heatmap = run("1105,1,1000000000000");
+        assert_eq!(heatmap.executes(1000000000000), 1);
+        assert_eq!(heatmap.len(), 1000000000001);
+        assert_eq!(heatmap.to_ppm(3, 4, 1).len(), b"P6\n4 1\n255\n".len() + 4 * 3);
+    }
+
+    #[test]
+    fn ppm() {
+        let mut heatmap = Heatmap::new();
+        heatmap.record_execute(0, 2);
+        heatmap.record_write(5);
+        heatmap.record_write(5);
+        heatmap.record_read(4);
+
+        let image = heatmap.to_ppm(heatmap.len(), 4, 2);
+        let header = b"P6\n8 4\n255\n";
+        assert!(image.starts_with(header));
+        let pixels = &image[header.len()..];
+        assert_eq!(pixels.len(), 8 * 4 * 3);
+
+        // Address 0 is the first two pixels of the first two lines
+        assert_eq!(&pixels[0..3], &[0, 0, 255]);
+        assert_eq!(&pixels[3..6], &[0, 0, 255]);
+        assert_eq!(&pixels[24..27], &[0, 0, 255]);
+        // Address 5 is on the second row of addresses, one address in
+        let at = |x: usize, y: usize| &pixels[(y * 8 + x) * 3..(y * 8 + x) * 3 + 3];
+        assert_eq!(at(2, 2), &[255, 0, 0]);
+        assert_eq!(at(0, 2), &[0, 255, 0]);
+        assert_eq!(at(6, 3), &[0, 0, 0]);
+    }
+}
@@ -0,0 +1,369 @@
+/*!
+ * The table of instructions a machine understands, so dialects can leave out
+ * standard instructions or add their own without changing the interpreter.
+ *
+ * ```text
+ * let mut opcodes = OpcodeTable::default();
+ * opcodes.insert(20, Opcode::new("DBG", vec![Access::Read], |exec| {
+ *     eprintln!("{}: {}", exec.pc(), exec.value(0)?);
+ *     Ok(Effect::Continue)
+ * }))?;
+ * let machine = IntCodeMachine::from_text(program).with_opcodes(opcodes);
+ * ```
+ *
+ * Opcodes are the last two digits of an instruction's first word as usual,
+ * with the digits above them giving the modes of its parameters. The standard
+ * instructions are handlers in the table like any other, so a dialect can move
+ * or replace them, and its own instructions can do anything they can: read
+ * input, output, jump, move the relative base or halt.
+ */
+
+use super::{Exec, IntCodeError, Word};
+
+use std::convert::TryFrom;
+use std::sync::Arc;
+
+/** How an instruction uses one of its parameters */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    Write,
+}
+
+/** What happens after an instruction has executed */
+#[derive(Debug, Clone, PartialEq)]
+pub enum Effect<W> {
+    /** Continue with the next instruction */
+    Continue,
+    Jump(usize),
+    Output(W),
+    /** Stop the program, optionally with an exit code for the runner */
+    Halt(Option<W>),
+    /**
+     * There was no input for the instruction. Nothing it did takes effect, and
+     * it executes again once there is some.
+     */
+    AwaitInput,
+}
+
+type Handler<W> = dyn Fn(&mut Exec<W>) -> Result<Effect<W>, IntCodeError> + Send + Sync;
+
+/** The standard instructions, which analyses such as the optimiser understand */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Builtin {
+    Add,
+    Mul,
+    Input,
+    Output,
+    JumpNz,
+    JumpEz,
+    LessThan,
+    Equals,
+    AddRb,
+    Halt,
+}
+
+impl Builtin {
+    /** Each standard instruction with its usual opcode */
+    pub const ALL: [(usize, Builtin); 10] = [
+        (1, Builtin::Add),
+        (2, Builtin::Mul),
+        (3, Builtin::Input),
+        (4, Builtin::Output),
+        (5, Builtin::JumpNz),
+        (6, Builtin::JumpEz),
+        (7, Builtin::LessThan),
+        (8, Builtin::Equals),
+        (9, Builtin::AddRb),
+        (99, Builtin::Halt),
+    ];
+
+    /** The instruction, to be put in a table at any opcode */
+    pub fn opcode<W: Word>(self) -> Opcode<W> {
+        use Access::{Read, Write};
+
+        let flag = |b: bool| if b { W::one() } else { W::zero() };
+        let mut op = match self {
+            Builtin::Add => Opcode::new("ADD", vec![Read, Read, Write], |exec: &mut Exec<W>| {
+                let v = exec.value(0)?.checked_add(&exec.value(1)?).ok_or_else(|| exec.overflow())?;
+                exec.store(2, v)?;
+                Ok(Effect::Continue)
+            }),
+            Builtin::Mul => Opcode::new("MUL", vec![Read, Read, Write], |exec: &mut Exec<W>| {
+                let v = exec.value(0)?.checked_mul(&exec.value(1)?).ok_or_else(|| exec.overflow())?;
+                exec.store(2, v)?;
+                Ok(Effect::Continue)
+            }),
+            Builtin::Input => Opcode::new("IN", vec![Write], |exec: &mut Exec<W>| match exec.input() {
+                Some(v) => {
+                    exec.store(0, v)?;
+                    Ok(Effect::Continue)
+                }
+                None => Ok(Effect::AwaitInput),
+            }),
+            Builtin::Output => Opcode::new("OUT", vec![Read], |exec: &mut Exec<W>| Ok(Effect::Output(exec.value(0)?))),
+            Builtin::JumpNz => Opcode::new("JNZ", vec![Read, Read], |exec: &mut Exec<W>| {
+                Ok(if exec.value(0)?.is_zero() {
+                    Effect::Continue
+                } else {
+                    Effect::Jump(exec.target(1)?)
+                })
+            }),
+            Builtin::JumpEz => Opcode::new("JZ", vec![Read, Read], |exec: &mut Exec<W>| {
+                Ok(if exec.value(0)?.is_zero() {
+                    Effect::Jump(exec.target(1)?)
+                } else {
+                    Effect::Continue
+                })
+            }),
+            Builtin::LessThan => Opcode::new("LT", vec![Read, Read, Write], move |exec: &mut Exec<W>| {
+                let v = flag(exec.value(0)? < exec.value(1)?);
+                exec.store(2, v)?;
+                Ok(Effect::Continue)
+            }),
+            Builtin::Equals => Opcode::new("EQ", vec![Read, Read, Write], move |exec: &mut Exec<W>| {
+                let v = flag(exec.value(0)? == exec.value(1)?);
+                exec.store(2, v)?;
+                Ok(Effect::Continue)
+            }),
+            Builtin::AddRb => Opcode::new("ARB", vec![Read], |exec: &mut Exec<W>| {
+                let r = exec.value(0)?.to_i64().ok_or_else(|| exec.overflow())?;
+                let rb = (exec.rb() as i64).checked_add(r).ok_or_else(|| exec.overflow())?;
+                exec.set_rb(usize::try_from(rb).map_err(|_| exec.invalid_address())?);
+                Ok(Effect::Continue)
+            }),
+            Builtin::Halt => Opcode::new("HLT", vec![], |_: &mut Exec<W>| Ok(Effect::Halt(None))),
+        };
+        op.builtin = Some(self);
+        op
+    }
+}
+
+/** An instruction, with the function which executes it */
+pub struct Opcode<W> {
+    name: String,
+    params: Vec<Access>,
+    handler: Box<Handler<W>>,
+    builtin: Option<Builtin>,
+}
+
+impl<W> Opcode<W> {
+    pub fn new<F>(name: &str, params: Vec<Access>, handler: F) -> Self
+    where
+        F: Fn(&mut Exec<W>) -> Result<Effect<W>, IntCodeError> + Send + Sync + 'static,
+    {
+        Self {
+            name: name.to_string(),
+            params,
+            handler: Box::new(handler),
+            builtin: None,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn params(&self) -> &[Access] {
+        &self.params
+    }
+
+    /** Which standard instruction this is, if it is one */
+    pub fn builtin(&self) -> Option<Builtin> {
+        self.builtin
+    }
+
+    pub fn call(&self, exec: &mut Exec<W>) -> Result<Effect<W>, IntCodeError> {
+        (self.handler)(exec)
+    }
+}
+
+#[derive(Clone)]
+pub struct OpcodeTable<W> {
+    entries: Vec<Option<Arc<Opcode<W>>>>,
+    /** Executed for words which aren't in the table */
+    halt: Arc<Opcode<W>>,
+}
+
+impl<W: Word> OpcodeTable<W> {
+    /** A table with no instructions at all */
+    pub fn empty() -> Self {
+        Self {
+            entries: (0..100).map(|_| None).collect(),
+            halt: Arc::new(Builtin::Halt.opcode()),
+        }
+    }
+
+    /** A table with only the given standard instructions */
+    pub fn builtins(opcodes: &[usize]) -> Self {
+        let mut table = Self::empty();
+        for (op, builtin) in Builtin::ALL.iter().filter(|(op, _)| opcodes.contains(op)) {
+            table.entries[*op] = Some(Arc::new(builtin.opcode()));
+        }
+        table
+    }
+
+    /** The day 2 machine, which only has ADD, MUL and HLT */
+    pub fn day2() -> Self {
+        Self::builtins(&[1, 2, 99])
+    }
+
+    /** Add an instruction, replacing any instruction with the same opcode */
+    pub fn insert(&mut self, opcode: usize, op: Opcode<W>) -> Result<(), String> {
+        if opcode == 0 || opcode >= 100 {
+            return Err(format!("opcode {} is not between 1 and 99", opcode));
+        }
+        self.entries[opcode] = Some(Arc::new(op));
+        Ok(())
+    }
+
+    pub fn remove(&mut self, opcode: usize) {
+        if let Some(entry) = self.entries.get_mut(opcode) {
+            *entry = None;
+        }
+    }
+
+    pub fn get(&self, opcode: usize) -> Option<&Arc<Opcode<W>>> {
+        self.entries.get(opcode)?.as_ref()
+    }
+
+    /** What a word without an opcode in the table executes as, which halts */
+    pub fn halt(&self) -> &Arc<Opcode<W>> {
+        &self.halt
+    }
+
+    /** Whether the instruction word (including modes) has an opcode in the table */
+    pub fn contains(&self, word: &W) -> bool {
+        match word.to_i64() {
+            Some(word) if word >= 0 => self.get((word % 100) as usize).is_some(),
+            _ => false,
+        }
+    }
+}
+
+impl<W: Word> Default for OpcodeTable<W> {
+    fn default() -> Self {
+        Self::builtins(&Builtin::ALL.map(|(op, _)| op))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::intcode::disasm::disassemble;
+    use crate::intcode::{IntCodeIO, IntCodeMachine, IntCodeRunner};
+    use std::sync::mpsc::channel;
+    use std::sync::Mutex;
+
+    fn run(machine: IntCodeMachine) -> (Vec<i64>, IntCodeRunner) {
+        let (_itx, irx) = channel();
+        let mut runner = IntCodeRunner::new(machine, irx);
+        let mut outputs = Vec::new();
+        while let IntCodeIO::Output(v) = runner.next() {
+            outputs.push(v);
+        }
+        (outputs, runner)
+    }
+
+    #[test]
+    fn day2_subset() {
+        let program = "1,9,10,3,2,3,11,0,99,30,40,50";
+        let machine = IntCodeMachine::load_file(program.to_string()).with_opcodes(OpcodeTable::day2());
+        let (_, runner) = run(machine);
+        assert_eq!(runner.machine.load(0), 3500);
+
+        // OUT isn't part of the subset, so reads as data and halts
+        let machine = IntCodeMachine::load_file("104,1,99".to_string()).with_opcodes(OpcodeTable::day2());
+        assert_eq!(disassemble(&machine)[0].text, "DATA 104");
+        assert!(run(machine).0.is_empty());
+        assert!(!OpcodeTable::<i64>::day2().contains(&104));
+        assert!(OpcodeTable::<i64>::day2().contains(&1101));
+    }
+
+    #[test]
+    fn custom() {
+        let printed = Arc::new(Mutex::new(Vec::new()));
+        let log = printed.clone();
+
+        let mut opcodes = OpcodeTable::default();
+        opcodes
+            .insert(
+                20,
+                Opcode::new("DBG", vec![Access::Read], move |exec| {
+                    log.lock().unwrap().push((exec.pc(), exec.value(0)?));
+                    Ok(Effect::Continue)
+                }),
+            )
+            .unwrap();
+        opcodes
+            .insert(21, Opcode::new("EXIT", vec![Access::Read], |exec| Ok(Effect::Halt(Some(exec.value(0)?)))))
+            .unwrap();
+        // Stores the square of its first parameter in its second
+        opcodes
+            .insert(
+                22,
+                Opcode::new("SQR", vec![Access::Read, Access::Write], |exec| {
+                    let v = exec.value(0)?;
+                    exec.store(1, v * v)?;
+                    Ok(Effect::Continue)
+                }),
+            )
+            .unwrap();
+        assert!(opcodes.insert(100, Opcode::new("BAD", vec![], |_| Ok(Effect::Continue))).is_err());
+
+        let program = "122,7,11,20,11,104,5,21,10,99,0,0";
+        let machine = IntCodeMachine::load_file(program.to_string()).with_opcodes(opcodes);
+        let text: Vec<String> = disassemble(&machine).into_iter().map(|l| l.text).collect();
+        assert_eq!(text[..4], ["SQR 7, [11]", "DBG [11]", "OUT 5", "EXIT [10]"]);
+
+        let (outputs, runner) = run(machine);
+        assert_eq!(outputs, vec![5]);
+        assert_eq!(*printed.lock().unwrap(), vec![(3, 49)]);
+        assert_eq!(runner.exit_code, Some(0));
+        assert!(runner.finished);
+    }
+
+    #[test]
+    fn input_and_rb() {
+        let mut opcodes = OpcodeTable::default();
+        // Halts with twice its input as the exit code
+        opcodes
+            .insert(
+                30,
+                Opcode::new("DBL", vec![], |exec| match exec.input() {
+                    Some(v) => Ok(Effect::Halt(Some(v * 2))),
+                    None => Ok(Effect::AwaitInput),
+                }),
+            )
+            .unwrap();
+        // Sets the relative base outright, rather than adding to it
+        opcodes
+            .insert(
+                31,
+                Opcode::new("SRB", vec![Access::Read], |exec| {
+                    let rb = exec.target(0)?;
+                    exec.set_rb(rb);
+                    Ok(Effect::Continue)
+                }),
+            )
+            .unwrap();
+        // The standard OUT moved to another opcode
+        opcodes.remove(4);
+        opcodes.insert(40, Builtin::Output.opcode()).unwrap();
+
+        let program = "131,7,240,1,30,99,0,0,5";
+        let machine = IntCodeMachine::load_file(program.to_string()).with_opcodes(opcodes);
+        let text: Vec<String> = disassemble(&machine).into_iter().map(|l| l.text).collect();
+        assert_eq!(text[..3], ["SRB 7", "OUT [rb+1]", "DBL"]);
+
+        let (itx, irx) = channel();
+        let mut runner = IntCodeRunner::new(machine, irx);
+        assert_eq!(runner.next().unwrap_output(), 5);
+        assert!(matches!(runner.next(), IntCodeIO::Input));
+        assert_eq!(runner.machine.pc(), 4);
+        itx.send(21).unwrap();
+        assert!(matches!(runner.next(), IntCodeIO::Finished));
+        assert_eq!(runner.exit_code, Some(42));
+        assert_eq!(runner.machine.rb(), 7);
+    }
+}
@@ -0,0 +1,367 @@
+/*!
+ * Peephole optimisation of decoded instructions, and a runner which caches
+ * the optimised instructions rather than decoding every step.
+ *
+ * Each instruction is optimised on its own, using only the words it was
+ * decoded from and the size of memory:
+ *
+ * - arithmetic and comparisons of two immediates are folded into a store of
+ *   the result, unless it overflows
+ * - adding zero or multiplying by one is a copy, and a copy of an address onto
+ *   itself is removed if the address is already in memory (memory never
+ *   shrinks, so the store could only have extended it)
+ * - jumps on an immediate condition are either unconditional or removed, as is
+ *   `ARB 0`
+ *
+ * A cached instruction is only reused while memory still holds the words it was
+ * decoded from, so code the program rewrites is decoded and optimised again.
+ */
+
+use super::opcodes::Builtin;
+use super::{IntCodeError, IntCodeIO, IntCodeMachine, IntCodeRunner, Ins, Parameter, Word};
+
+use std::fmt;
+use std::sync::mpsc::Receiver;
+
+/** An instruction after peephole optimisation */
+#[derive(Clone)]
+enum Op<W> {
+    /** An instruction with nothing to optimise */
+    Ins(Ins<W>),
+    /** Store a value known ahead of time */
+    Set(W, Parameter<W>),
+    /** Store a value unchanged */
+    Copy(Parameter<W>, Parameter<W>),
+    Jump(Parameter<W>),
+    /** An instruction with no effect */
+    Skip,
+}
+
+impl<W: Word> fmt::Display for Op<W> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Op::Ins(ins) => write!(f, "{}", ins),
+            Op::Set(v, r) => write!(f, "SET {}, {}", v, r),
+            Op::Copy(a, r) => write!(f, "CPY {}, {}", a, r),
+            Op::Jump(t) => write!(f, "JMP {}", t),
+            Op::Skip => write!(f, "NOP"),
+        }
+    }
+}
+
+fn is<W: Word>(p: &Parameter<W>, v: i64) -> bool {
+    matches!(p, Parameter::Direct(d) if *d == W::from(v))
+}
+
+/** The result of an instruction whose inputs are both immediate */
+fn fold<W: Word>(ins: &Ins<W>) -> Option<(W, Parameter<W>)> {
+    let flag = |b: bool| if b { W::one() } else { W::zero() };
+    let (a, b, r) = match &ins.params[..] {
+        [Parameter::Direct(a), Parameter::Direct(b), r] => (a, b, r.clone()),
+        _ => return None,
+    };
+    match ins.builtin()? {
+        Builtin::Add => Some((a.checked_add(b)?, r)),
+        Builtin::Mul => Some((a.checked_mul(b)?, r)),
+        Builtin::LessThan => Some((flag(a < b), r)),
+        Builtin::Equals => Some((flag(a == b), r)),
+        _ => None,
+    }
+}
+
+/** A copy, removed if it stores an address's own value back within `memory_len` words */
+fn copy<W: Word>(a: Parameter<W>, r: Parameter<W>, memory_len: usize) -> Op<W> {
+    match (&a, &r) {
+        // Storing back to an invalid address is an error, and past the end of memory extends it
+        (Parameter::Position(x), Parameter::Position(y)) if x == y && x.to_usize().is_some_and(|x| x < memory_len) => {
+            Op::Skip
+        }
+        _ => Op::Copy(a, r),
+    }
+}
+
+/** Optimise `ins`, decoded from a machine with `memory_len` words of memory */
+fn peephole<W: Word>(ins: Ins<W>, memory_len: usize) -> Op<W> {
+    if let Some((v, r)) = fold(&ins) {
+        return Op::Set(v, r);
+    }
+
+    let copy = |a: &Parameter<W>, r: &Parameter<W>| copy(a.clone(), r.clone(), memory_len);
+    let op = match (ins.builtin(), &ins.params[..]) {
+        (Some(Builtin::Add), [a, b, r]) if is(b, 0) => Some(copy(a, r)),
+        (Some(Builtin::Add), [a, b, r]) if is(a, 0) => Some(copy(b, r)),
+        (Some(Builtin::Mul), [a, b, r]) if is(b, 1) => Some(copy(a, r)),
+        (Some(Builtin::Mul), [a, b, r]) if is(a, 1) => Some(copy(b, r)),
+        (Some(Builtin::JumpNz), [Parameter::Direct(c), t]) if !c.is_zero() => Some(Op::Jump(t.clone())),
+        (Some(Builtin::JumpEz), [Parameter::Direct(c), t]) if c.is_zero() => Some(Op::Jump(t.clone())),
+        (Some(Builtin::JumpNz | Builtin::JumpEz), [Parameter::Direct(_), _]) => Some(Op::Skip),
+        (Some(Builtin::AddRb), [r]) if is(r, 0) => Some(Op::Skip),
+        _ => None,
+    };
+    op.unwrap_or_else(|| Op::Ins(ins))
+}
+
+struct Entry<W> {
+    /** The words the instruction was decoded from */
+    raw: Vec<W>,
+    op: Op<W>,
+}
+
+/**
+ * Runs a machine from a cache of optimised instructions, behaving as an
+ * `IntCodeRunner`. Instrumentation set on `runner` only sees part of the run:
+ * stores are still recorded by its self modification tracker and heatmap, but
+ * as instructions aren't decoded every step their executes, reads and coverage
+ * aren't, and neither are stores which were optimised away.
+ */
+pub struct OptimisedRunner<W = i64> {
+    /** Executes instructions left unoptimised, and holds the machine */
+    pub runner: IntCodeRunner<W>,
+    /** Instructions by address. Only those within memory are cached, so far jumps stay cheap */
+    cache: Vec<Option<Entry<W>>>,
+    /** Instructions decoded so far, counting those decoded again after being rewritten */
+    pub translations: usize,
+}
+
+impl<W: Word> OptimisedRunner<W> {
+    pub fn new(machine: IntCodeMachine<W>, inputs: Receiver<W>) -> Self {
+        Self {
+            runner: IntCodeRunner::new(machine, inputs),
+            cache: Vec::new(),
+            translations: 0,
+        }
+    }
+
+    /** Run until next input instruction, stopping if the program faults */
+    pub fn try_next(&mut self) -> Result<IntCodeIO<W>, IntCodeError> {
+        loop {
+            if let Some(io) = self.try_step()? {
+                return Ok(io);
+            }
+        }
+    }
+
+    /** The optimised instruction at `pc` and its length, from the cache if it is still valid */
+    fn fetch(&mut self, pc: usize) -> Result<(Op<W>, usize), IntCodeError> {
+        let m = &self.runner.machine;
+        if let Some(Some(entry)) = self.cache.get(pc) {
+            if m.ops.get(pc..pc + entry.raw.len()) == Some(&entry.raw[..]) {
+                return Ok((entry.op.clone(), entry.raw.len()));
+            }
+        }
+
+        let (ins, len) = m.try_decode(pc)?;
+        let raw = (pc..pc + len).map(|a| m.load(a)).collect();
+        let op = peephole(ins, m.ops.len());
+        self.translations += 1;
+        if pc < m.ops.len() {
+            if pc >= self.cache.len() {
+                self.cache.resize_with(pc + 1, || None);
+            }
+            self.cache[pc] = Some(Entry { raw, op: op.clone() });
+        }
+        Ok((op, len))
+    }
+
+    /** Execute a single instruction, returning any IO event it caused */
+    pub fn try_step(&mut self) -> Result<Option<IntCodeIO<W>>, IntCodeError> {
+        if self.runner.expects_input() || self.runner.finished {
+            return self.runner.try_step();
+        }
+
+        let pc = self.runner.machine.pc;
+        let (op, len) = self.fetch(pc)?;
+        let r = &mut self.runner;
+        r.machine.pc = pc + len;
+        match op {
+            Op::Ins(ins) => return r.execute(pc, ins),
+            Op::Set(v, dst) => {
+                let addr = r.machine.address(&dst, pc)?;
+                r.write(pc, addr, v);
+            }
+            Op::Copy(src, dst) => {
+                let v = r.machine.value(&src, pc)?;
+                let addr = r.machine.address(&dst, pc)?;
+                r.write(pc, addr, v);
+            }
+            Op::Jump(target) => r.machine.pc = r.machine.jump_target(&target, pc)?,
+            Op::Skip => {}
+        }
+
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::intcode::conformance::parse_suite;
+    use std::sync::mpsc::channel;
+
+    fn optimised(ins: &str) -> String {
+        let m = IntCodeMachine::load_file(ins.to_string());
+        peephole(m.decode(0).unwrap().0, m.memory().len()).to_string()
+    }
+
+    #[test]
+    fn rules() {
+        assert_eq!(optimised("1101,3,4,9"), "SET 7, [9]");
+        assert_eq!(optimised("21102,3,4,9"), "SET 12, [rb+9]");
+        assert_eq!(optimised("1107,1,2,5"), "SET 1, [5]");
+        assert_eq!(optimised("1108,1,2,5"), "SET 0, [5]");
+        assert_eq!(optimised("1101,9223372036854775807,1,0"), "ADD 9223372036854775807, 1, [0]");
+
+        assert_eq!(optimised("1001,4,0,9"), "CPY [4], [9]");
+        assert_eq!(optimised("102,1,4,9"), "CPY [4], [9]");
+        assert_eq!(optimised("1001,3,0,3"), "NOP");
+        assert_eq!(optimised("1001,4,0,4"), "CPY [4], [4]");
+        assert_eq!(optimised("1001,-4,0,-4"), "CPY [-4], [-4]");
+        assert_eq!(optimised("22101,0,4,4"), "CPY [rb+4], [rb+4]");
+
+        assert_eq!(optimised("1105,1,7"), "JMP 7");
+        assert_eq!(optimised("106,0,5"), "JMP [5]");
+        assert_eq!(optimised("1105,0,7"), "NOP");
+        assert_eq!(optimised("1106,3,7"), "NOP");
+        assert_eq!(optimised("1006,3,7"), "JZ  [3], 7");
+        assert_eq!(optimised("109,0"), "NOP");
+        assert_eq!(optimised("109,1"), "ARB 1");
+    }
+
+    /** Everything observable about a run: outputs, how it stopped and the final memory */
+    #[derive(Debug, PartialEq)]
+    struct Run {
+        outputs: Vec<i64>,
+        end: Result<String, IntCodeError>,
+        memory: Vec<i64>,
+    }
+
+    fn observe<F>(mut step: F, limit: usize) -> (Vec<i64>, Result<String, IntCodeError>)
+    where
+        F: FnMut() -> Result<Option<IntCodeIO>, IntCodeError>,
+    {
+        let mut outputs = Vec::new();
+        for _ in 0..limit {
+            match step() {
+                Ok(Some(IntCodeIO::Output(v))) => outputs.push(v),
+                Ok(Some(IntCodeIO::Input)) => return (outputs, Ok("input".to_string())),
+                Ok(Some(IntCodeIO::Finished)) => return (outputs, Ok("finished".to_string())),
+                Ok(None) => {}
+                Err(e) => return (outputs, Err(e)),
+            }
+        }
+        (outputs, Ok("step limit".to_string()))
+    }
+
+    /** Run `program` with both runners */
+    fn both(program: &str, inputs: &[i64], limit: usize) -> (Run, Run) {
+        let machine = IntCodeMachine::load_file(program.to_string());
+        let (itx, irx) = channel();
+        let mut plain = IntCodeRunner::new(machine.clone(), irx);
+        let (otx, orx) = channel();
+        let mut fast = OptimisedRunner::new(machine, orx);
+        for v in inputs {
+            itx.send(*v).unwrap();
+            otx.send(*v).unwrap();
+        }
+
+        let (outputs, end) = observe(|| plain.try_step(), limit);
+        let a = Run {
+            outputs,
+            end,
+            memory: plain.machine.memory().to_vec(),
+        };
+        let (outputs, end) = observe(|| fast.try_step(), limit);
+        let b = Run {
+            outputs,
+            end,
+            memory: fast.runner.machine.memory().to_vec(),
+        };
+        (a, b)
+    }
+
+    fn assert_same(program: &str, inputs: &[i64], limit: usize) {
+        let (plain, fast) = both(program, inputs, limit);
+        assert_eq!(plain, fast, "program {}", program);
+    }
+
+    #[test]
+    fn differential_corpus() {
+        let cases = parse_suite(include_str!("../../conformance/intcode.txt")).unwrap();
+        for case in cases {
+            assert_same(&case.program, &case.input, 10_000);
+        }
+    }
+
+    #[test]
+    fn differential_inputs() {
+        // Day 5's diagnostics rewrite their own code
+        let day5 = include_str!("../../../day_05/input.txt").trim();
+        assert_same(day5, &[1], 100_000);
+        assert_same(day5, &[5], 100_000);
+        let (_, fast) = both(day5, &[5], 100_000);
+        assert_eq!(fast.outputs.len(), 1);
+
+        let day9 = include_str!("../../../day_09/input.txt").trim();
+        assert_same(day9, &[1], 100_000);
+    }
+
+    #[test]
+    fn differential_random() {
+        // Small random programs, biased towards immediates, zeros and ones to hit the rules
+        let mut seed: u64 = 12345;
+        let mut rand = |n: u64| {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (seed >> 33) % n
+        };
+
+        for _ in 0..3000 {
+            let len = 8 + rand(24);
+            let mut words = Vec::new();
+            while (words.len() as u64) < len {
+                let op = [1, 2, 3, 4, 5, 6, 7, 8, 9, 99][rand(10) as usize];
+                let count = match op {
+                    1 | 2 | 7 | 8 => 3,
+                    5 | 6 => 2,
+                    3 | 4 | 9 => 1,
+                    _ => 0,
+                };
+                let mut opcode = op;
+                for i in 0..count {
+                    opcode += [0, 0, 1, 1, 1, 2][rand(6) as usize] * 10i64.pow(i + 2);
+                }
+                words.push(opcode);
+                for _ in 0..count {
+                    words.push([0, 0, 1, 1, -1, rand(len + 2) as i64, rand(len) as i64][rand(7) as usize]);
+                }
+            }
+
+            let program = words.iter().map(|w| w.to_string()).collect::<Vec<String>>().join(",");
+            assert_same(&program, &[3, 0, 1], 300);
+        }
+    }
+
+    #[test]
+    fn rewritten_code() {
+        // Adds 6 to the operand of the OUT at 0 until it reaches 20
+        let program = "104,1,1001,1,6,1,1007,1,20,14,1005,14,0,99,0";
+        let (itx, irx) = channel::<i64>();
+        drop(itx);
+        let mut runner = OptimisedRunner::new(IntCodeMachine::load_file(program.to_string()), irx);
+        let mut outputs = Vec::new();
+        while let IntCodeIO::Output(v) = runner.try_next().unwrap() {
+            outputs.push(v);
+        }
+
+        assert_eq!(outputs, vec![1, 7, 13, 19]);
+        // Five instructions, with the OUT decoded again after each of its three rewrites
+        assert_eq!(runner.translations, 8);
+    }
+
+    #[test]
+    fn far_jump() {
+        // Halts on the zero word far past the end of memory
+        assert_same("1105,1,1000000000000", &[], 10);
+        // Copies the word past the end of memory onto itself, extending memory
+        assert_same("1001,5,0,5,99", &[], 10);
+    }
+}
@@ -0,0 +1,211 @@
+use super::{IntCodeError, IntCodeIO, IntCodeMachine, IntCodeRunner, Word};
+
+use std::sync::mpsc::{channel, Sender};
+
+pub type MachineId = usize;
+
+/** Default number of instructions a machine may run before yielding to others */
+pub const DEFAULT_TIME_SLICE: usize = 1000;
+
+#[derive(Debug, PartialEq)]
+pub enum Event<W = i64> {
+    Output(MachineId, W),
+    Finished(MachineId),
+    Error(MachineId, IntCodeError),
+    /** Every machine which hasn't finished is blocked on input with nothing queued */
+    Quiescent,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum State {
+    Ready,
+    Blocked,
+    Finished,
+}
+
+struct Task<W> {
+    runner: IntCodeRunner<W>,
+    input: Sender<W>,
+    state: State,
+    priority: u32,
+    time_slice: usize,
+}
+
+/**
+ * Cooperative scheduler multiplexing any number of intcode machines on one thread.
+ * Each machine runs until it produces output, blocks on input or uses up its time slice.
+ * Higher priority machines always run first; equal priorities take turns.
+ */
+pub struct Scheduler<W = i64> {
+    tasks: Vec<Task<W>>,
+    cursor: usize,
+}
+
+impl<W: Word> Default for Scheduler<W> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<W: Word> Scheduler<W> {
+    pub fn new() -> Self {
+        Self {
+            tasks: Vec::new(),
+            cursor: 0,
+        }
+    }
+
+    pub fn spawn(&mut self, machine: IntCodeMachine<W>) -> MachineId {
+        self.spawn_with(machine, 0, DEFAULT_TIME_SLICE)
+    }
+
+    pub fn spawn_with(&mut self, machine: IntCodeMachine<W>, priority: u32, time_slice: usize) -> MachineId {
+        let (itx, irx) = channel();
+        self.tasks.push(Task {
+            runner: IntCodeRunner::new(machine, irx),
+            input: itx,
+            state: State::Ready,
+            priority,
+            time_slice: time_slice.max(1),
+        });
+        self.tasks.len() - 1
+    }
+
+    /** Queue input for a machine, waking it if it was blocked */
+    pub fn send(&mut self, id: MachineId, value: W) {
+        let task = &mut self.tasks[id];
+        if task.state == State::Finished {
+            return;
+        }
+        task.input.send(value).unwrap();
+        task.state = State::Ready;
+    }
+
+    pub fn runner(&self, id: MachineId) -> &IntCodeRunner<W> {
+        &self.tasks[id].runner
+    }
+
+    pub fn runner_mut(&mut self, id: MachineId) -> &mut IntCodeRunner<W> {
+        &mut self.tasks[id].runner
+    }
+
+    pub fn len(&self) -> usize {
+        self.tasks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tasks.is_empty()
+    }
+
+    /** Highest priority ready machine, taking turns from the cursor among equals */
+    fn pick(&self) -> Option<MachineId> {
+        let n = self.tasks.len();
+        let mut best: Option<MachineId> = None;
+        for i in 0..n {
+            let id = (self.cursor + i) % n;
+            let task = &self.tasks[id];
+            if task.state != State::Ready {
+                continue;
+            }
+            if best.is_none_or(|b| task.priority > self.tasks[b].priority) {
+                best = Some(id);
+            }
+        }
+        best
+    }
+
+    /**
+     * Run machines until something happens.
+     * Returns None once every machine has finished.
+     */
+    pub fn run(&mut self) -> Option<Event<W>> {
+        loop {
+            let id = match self.pick() {
+                Some(id) => id,
+                None if self.tasks.iter().all(|t| t.state == State::Finished) => return None,
+                None => return Some(Event::Quiescent),
+            };
+            self.cursor = (id + 1) % self.tasks.len();
+
+            let task = &mut self.tasks[id];
+            for _ in 0..task.time_slice {
+                match task.runner.try_step() {
+                    Ok(None) => {}
+                    Ok(Some(IntCodeIO::Output(v))) => return Some(Event::Output(id, v)),
+                    Ok(Some(IntCodeIO::Input)) => {
+                        task.state = State::Blocked;
+                        break;
+                    }
+                    Ok(Some(IntCodeIO::Finished)) => {
+                        task.state = State::Finished;
+                        return Some(Event::Finished(id));
+                    }
+                    Err(e) => {
+                        task.state = State::Finished;
+                        return Some(Event::Error(id, e));
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // Reads a number, outputs it plus one, forever
+    const INCREMENT: &str = "3,11,1001,11,1,11,4,11,1105,1,0";
+
+    #[test]
+    fn ring() {
+        let mut sched = Scheduler::new();
+        let machine = IntCodeMachine::load_file(INCREMENT.to_string());
+        for _ in 0..100 {
+            sched.spawn(machine.clone());
+        }
+
+        // Pass a counter all the way around the ring
+        sched.send(0, 0);
+        let mut last = None;
+        loop {
+            match sched.run().unwrap() {
+                Event::Output(id, v) if id + 1 < sched.len() => sched.send(id + 1, v),
+                Event::Output(_, v) => last = Some(v),
+                Event::Quiescent => break,
+                e => panic!("Unexpected {:?}", e),
+            }
+        }
+        assert_eq!(last, Some(100));
+    }
+
+    #[test]
+    fn priorities_and_finish() {
+        let mut sched = Scheduler::new();
+        let low = sched.spawn_with(IntCodeMachine::load_file("104,1,99".to_string()), 0, 1);
+        let high = sched.spawn_with(IntCodeMachine::load_file("104,2,99".to_string()), 5, 1);
+
+        assert_eq!(sched.run(), Some(Event::Output(high, 2)));
+        assert_eq!(sched.run(), Some(Event::Finished(high)));
+        assert_eq!(sched.run(), Some(Event::Output(low, 1)));
+        assert_eq!(sched.run(), Some(Event::Finished(low)));
+        assert_eq!(sched.run(), None);
+    }
+
+    #[test]
+    fn time_slices_interleave() {
+        // Two infinite loops which each output after every few instructions
+        let counter = "1001,9,1,9,4,9,1105,1,0,0";
+        let mut sched = Scheduler::new();
+        let a = sched.spawn_with(IntCodeMachine::load_file(counter.to_string()), 0, 2);
+        let b = sched.spawn_with(IntCodeMachine::load_file(counter.to_string()), 0, 2);
+
+        let mut outputs = Vec::new();
+        for _ in 0..4 {
+            if let Some(Event::Output(id, v)) = sched.run() {
+                outputs.push((id, v));
+            }
+        }
+        assert_eq!(outputs, vec![(a, 1), (b, 1), (a, 2), (b, 2)]);
+    }
+}
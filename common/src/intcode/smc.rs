@@ -0,0 +1,137 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SelfModKind {
+    /** An instruction wrote to an address which had already been executed */
+    PatchedExecuted,
+    /** An instruction was executed from an address which had been written to */
+    ExecutedWritten { exec_pc: usize },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SelfModEvent {
+    pub kind: SelfModKind,
+    pub writer_pc: usize,
+    pub target: usize,
+}
+
+impl fmt::Display for SelfModEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.kind {
+            SelfModKind::PatchedExecuted => write!(
+                f,
+                "pc {} wrote to {} which was already executed",
+                self.writer_pc, self.target
+            ),
+            SelfModKind::ExecutedWritten { exec_pc } => write!(
+                f,
+                "pc {} executed {} which was written by pc {}",
+                exec_pc, self.target, self.writer_pc
+            ),
+        }
+    }
+}
+
+/**
+ * Tracks self modifying code: writes to addresses which were part of executed
+ * instructions, and execution of instructions containing written addresses.
+ * Each write is reported as executed at most once, so loops over patched code
+ * don't flood the log.
+ */
+#[derive(Clone, Default, Debug)]
+pub struct SelfModTracker {
+    executed: HashSet<usize>,
+    written_by: HashMap<usize, usize>,
+    pub events: Vec<SelfModEvent>,
+}
+
+impl SelfModTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record_execute(&mut self, pc: usize, len: usize) {
+        for addr in pc..pc + len {
+            self.executed.insert(addr);
+            if let Some(writer_pc) = self.written_by.remove(&addr) {
+                self.events.push(SelfModEvent {
+                    kind: SelfModKind::ExecutedWritten { exec_pc: pc },
+                    writer_pc,
+                    target: addr,
+                });
+            }
+        }
+    }
+
+    pub fn record_write(&mut self, pc: usize, addr: usize) {
+        if self.executed.contains(&addr) {
+            self.events.push(SelfModEvent {
+                kind: SelfModKind::PatchedExecuted,
+                writer_pc: pc,
+                target: addr,
+            });
+        }
+        self.written_by.insert(addr, pc);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::intcode::{IntCodeIO, IntCodeMachine, IntCodeRunner};
+    use std::sync::mpsc::channel;
+
+    fn events(program: &str) -> Vec<SelfModEvent> {
+        let (_itx, irx) = channel();
+        let mut runner = IntCodeRunner::new(IntCodeMachine::load_file(program.to_string()), irx);
+        runner.smc = Some(SelfModTracker::new());
+        while let IntCodeIO::Output(_) = runner.next() {}
+        runner.smc.unwrap().events
+    }
+
+    #[test]
+    fn patch_ahead() {
+        // Overwrites the 33 at address 4 with 99 before executing it
+        assert_eq!(events("1002,4,3,4,33"), vec![SelfModEvent {
+            kind: SelfModKind::ExecutedWritten { exec_pc: 4 },
+            writer_pc: 0,
+            target: 4,
+        }]);
+    }
+
+    #[test]
+    fn patch_behind() {
+        // The day 9 quine keeps its counter at address 100, outside of its code,
+        // so nothing is reported
+        let quine = "109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99";
+        assert_eq!(events(quine), vec![]);
+
+        // Loop which counts up in its own immediate operand until it reaches 3,
+        // then outputs the result of the comparison it patched into the OUT
+        let program = "1101,0,1,1,1008,1,3,12,1006,12,0,104,0,99";
+        let ev = events(program);
+        assert_eq!(ev[0], SelfModEvent {
+            kind: SelfModKind::PatchedExecuted,
+            writer_pc: 0,
+            target: 1,
+        });
+        assert_eq!(ev[1], SelfModEvent {
+            kind: SelfModKind::ExecutedWritten { exec_pc: 0 },
+            writer_pc: 0,
+            target: 1,
+        });
+        assert_eq!(ev[5], SelfModEvent {
+            kind: SelfModKind::ExecutedWritten { exec_pc: 11 },
+            writer_pc: 4,
+            target: 12,
+        });
+        assert_eq!(ev.len(), 6);
+    }
+
+    #[test]
+    fn far_jump() {
+        // Halts on the zero word far past the end of memory
+        assert_eq!(events("1105,1,1000000000000"), vec![]);
+    }
+}
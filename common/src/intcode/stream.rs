@@ -0,0 +1,148 @@
+/*!
+ * An async interface to the runner. Input is awaited from any `Stream` and
+ * output is produced as a `Stream`, so many machines can run as tasks on one
+ * executor instead of each needing its own thread.
+ */
+
+use super::{IntCodeError, IntCodeIO, IntCodeMachine, IntCodeRunner, Word};
+
+use futures::stream::Stream;
+use futures::task::{Context, Poll};
+use std::pin::Pin;
+use std::sync::mpsc::{channel, Sender};
+
+/** Instructions to execute before letting other tasks on the executor run */
+const STEP_BUDGET: usize = 10_000;
+
+/**
+ * Runs a machine as a stream of its outputs, ending when the machine halts, or
+ * when it wants input and `inputs` has ended. A fault is yielded as an error
+ * and ends the stream.
+ */
+pub struct AsyncRunner<W, S> {
+    pub runner: IntCodeRunner<W>,
+    inputs: S,
+    /** Feeds values from `inputs` to the runner as it asks for them */
+    itx: Sender<W>,
+    done: bool,
+}
+
+impl<W: Word, S: Stream<Item = W> + Unpin> AsyncRunner<W, S> {
+    pub fn new(machine: IntCodeMachine<W>, inputs: S) -> Self {
+        let (itx, irx) = channel();
+        Self {
+            runner: IntCodeRunner::new(machine, irx),
+            inputs,
+            itx,
+            done: false,
+        }
+    }
+}
+
+impl<W: Word + Unpin, S: Stream<Item = W> + Unpin> Stream for AsyncRunner<W, S> {
+    type Item = Result<W, IntCodeError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        if this.done {
+            return Poll::Ready(None);
+        }
+
+        for _ in 0..STEP_BUDGET {
+            match this.runner.try_step() {
+                Ok(None) => {}
+                Ok(Some(IntCodeIO::Output(v))) => return Poll::Ready(Some(Ok(v))),
+                Ok(Some(IntCodeIO::Input)) => match Pin::new(&mut this.inputs).poll_next(cx) {
+                    // The runner picks the value up on its next step
+                    Poll::Ready(Some(v)) => this.itx.send(v).unwrap(),
+                    Poll::Ready(None) => {
+                        this.done = true;
+                        return Poll::Ready(None);
+                    }
+                    Poll::Pending => return Poll::Pending,
+                },
+                Ok(Some(IntCodeIO::Finished)) => {
+                    this.done = true;
+                    return Poll::Ready(None);
+                }
+                Err(e) => {
+                    this.done = true;
+                    return Poll::Ready(Some(Err(e)));
+                }
+            }
+        }
+
+        // Still running, but give other tasks a turn before carrying on
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use futures::channel::mpsc::unbounded;
+    use futures::executor::{block_on, LocalPool};
+    use futures::stream::{self, StreamExt};
+    use futures::task::LocalSpawnExt;
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    fn machine(program: &str) -> IntCodeMachine {
+        IntCodeMachine::load_file(program.to_string())
+    }
+
+    #[test]
+    fn outputs() {
+        let quine = "109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99";
+        let runner = AsyncRunner::new(machine(quine), stream::empty());
+        let outputs: Vec<i64> = block_on(runner.map(Result::unwrap).collect());
+        assert_eq!(outputs, machine(quine).memory());
+
+        // Day 5's "is the input 8" program
+        let runner = AsyncRunner::new(machine("3,9,8,9,10,9,4,9,99,-1,8"), stream::iter(vec![8]));
+        assert_eq!(block_on(runner.collect::<Vec<_>>()), vec![Ok(1)]);
+
+        // Running out of input ends the stream
+        let runner = AsyncRunner::new(machine("3,9,8,9,10,9,4,9,99,-1,8"), stream::empty());
+        assert!(block_on(runner.collect::<Vec<_>>()).is_empty());
+
+        let runner = AsyncRunner::new(machine("4,-1,99"), stream::empty());
+        let results = block_on(runner.collect::<Vec<_>>());
+        assert!(matches!(results[..], [Err(IntCodeError::InvalidAddress { pc: 0, .. })]));
+    }
+
+    #[test]
+    fn feedback_loop() {
+        // Day 7's amplifiers in a feedback loop, each a task on one thread
+        let program = "3,26,1001,26,-4,26,3,27,1002,27,2,27,1,27,26,27,4,27,1001,28,-1,28,1005,28,6,99,0,0,5";
+        let channels: Vec<_> = (0..5).map(|_| unbounded()).collect();
+        let (senders, receivers): (Vec<_>, Vec<_>) = channels.into_iter().unzip();
+        for (tx, phase) in senders.iter().zip(&[9, 8, 7, 6, 5]) {
+            tx.unbounded_send(*phase).unwrap();
+        }
+        senders[0].unbounded_send(0).unwrap();
+
+        let mut pool = LocalPool::new();
+        let last = Rc::new(Cell::new(0));
+        for (i, irx) in receivers.into_iter().enumerate() {
+            let next = senders[(i + 1) % 5].clone();
+            let last = last.clone();
+            let mut amp = AsyncRunner::new(machine(program), irx);
+            pool.spawner()
+                .spawn_local(async move {
+                    while let Some(v) = amp.next().await {
+                        let v = v.unwrap();
+                        last.set(v);
+                        // The first amplifier may already have halted
+                        let _ = next.unbounded_send(v);
+                    }
+                })
+                .unwrap();
+        }
+        drop(senders);
+        pool.run();
+
+        assert_eq!(last.get(), 139629729);
+    }
+}
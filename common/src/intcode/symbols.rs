@@ -0,0 +1,241 @@
+/*!
+ * Symbol tables naming and annotating addresses of an intcode program.
+ *
+ * They live in a sidecar file next to the program (`input.txt.sym` for
+ * `input.txt`) so what we learn about a program survives between sessions:
+ *
+ * ```text
+ * # Lines starting with a hash are ignored
+ * 0x1f4: room_table
+ * pc 1050: print_string ; prints the string at [rb+1]
+ * 1062: ; just a comment
+ * ```
+ *
+ * Addresses are decimal or `0x` hex. A `pc` prefix marks the address as code,
+ * which the disassembler shows as a label; other names are data. Anything after
+ * `;` is a comment shown alongside the address.
+ */
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Symbol {
+    pub name: Option<String>,
+    pub comment: Option<String>,
+    pub code: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Symbols {
+    entries: BTreeMap<usize, Symbol>,
+}
+
+fn parse_addr(s: &str) -> Option<usize> {
+    match s.strip_prefix("0x") {
+        Some(hex) => usize::from_str_radix(hex, 16).ok(),
+        None => s.parse().ok(),
+    }
+}
+
+fn is_valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_alphabetic() || c == '_')
+        && chars.all(|c| c.is_alphanumeric() || c == '_' || c == '.')
+}
+
+impl Symbols {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn parse(contents: &str) -> Result<Self, String> {
+        let mut symbols = Self::new();
+        for (i, line) in contents.lines().enumerate() {
+            let n = i + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (line, comment) = match line.find(';') {
+                Some(p) => (line[..p].trim(), Some(line[p + 1..].trim())),
+                None => (line, None),
+            };
+            let (addr, name) = match line.find(':') {
+                Some(p) => (line[..p].trim(), line[p + 1..].trim()),
+                None => return Err(format!("line {}: expected '<address>: <name>', got '{}'", n, line)),
+            };
+            let (addr, code) = match addr.strip_prefix("pc") {
+                Some(a) => (a.trim(), true),
+                None => (addr, false),
+            };
+
+            let addr = parse_addr(addr).ok_or_else(|| format!("line {}: invalid address '{}'", n, addr))?;
+            if !name.is_empty() {
+                symbols.set_name(addr, name, code).map_err(|e| format!("line {}: {}", n, e))?;
+            }
+            if let Some(comment) = comment.filter(|c| !c.is_empty()) {
+                symbols.set_comment(addr, comment);
+            }
+            if code {
+                // Kept for unnamed addresses too, so they are still marked as code
+                symbols.entries.entry(addr).or_default().code = true;
+            }
+        }
+
+        Ok(symbols)
+    }
+
+    pub fn sidecar_path(program: &str) -> String {
+        format!("{}.sym", program)
+    }
+
+    /** Load the sidecar for `program`, which is empty if there isn't one yet */
+    pub fn load_sidecar(program: &str) -> Result<Self, String> {
+        let path = Self::sidecar_path(program);
+        if !Path::new(&path).exists() {
+            return Ok(Self::new());
+        }
+        let contents = std::fs::read_to_string(&path).map_err(|e| format!("Couldn't read {}: {}", path, e))?;
+        Self::parse(&contents).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    pub fn save_sidecar(&self, program: &str) -> Result<(), String> {
+        let path = Self::sidecar_path(program);
+        std::fs::write(&path, self.to_string()).map_err(|e| format!("Couldn't write {}: {}", path, e))
+    }
+
+    pub fn get(&self, addr: usize) -> Option<&Symbol> {
+        self.entries.get(&addr)
+    }
+
+    pub fn name(&self, addr: usize) -> Option<&str> {
+        self.get(addr)?.name.as_deref()
+    }
+
+    pub fn comment(&self, addr: usize) -> Option<&str> {
+        self.get(addr)?.comment.as_deref()
+    }
+
+    /** Name of `addr` if it is marked as code */
+    pub fn label(&self, addr: usize) -> Option<&str> {
+        self.get(addr).filter(|s| s.code)?.name.as_deref()
+    }
+
+    /** The address with the given name */
+    pub fn address(&self, name: &str) -> Option<usize> {
+        self.entries
+            .iter()
+            .find(|(_, s)| s.name.as_deref() == Some(name))
+            .map(|(a, _)| *a)
+    }
+
+    /** Read a number or a symbol name as an address */
+    pub fn resolve(&self, s: &str) -> Option<usize> {
+        parse_addr(s).or_else(|| self.address(s))
+    }
+
+    /** Name `addr`, failing if the name is malformed or already names another address */
+    pub fn set_name(&mut self, addr: usize, name: &str, code: bool) -> Result<(), String> {
+        if !is_valid_name(name) {
+            return Err(format!("invalid name '{}'", name));
+        }
+        if let Some(other) = self.address(name).filter(|a| *a != addr) {
+            return Err(format!("'{}' is already the name of {}", name, other));
+        }
+
+        let symbol = self.entries.entry(addr).or_default();
+        symbol.name = Some(name.to_string());
+        symbol.code = code;
+        Ok(())
+    }
+
+    pub fn set_comment(&mut self, addr: usize, comment: &str) {
+        self.entries.entry(addr).or_default().comment = Some(comment.to_string());
+    }
+
+    pub fn insert(&mut self, addr: usize, symbol: Symbol) {
+        self.entries.insert(addr, symbol);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (usize, &Symbol)> {
+        self.entries.iter().map(|(a, s)| (*a, s))
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+/** Writes the sidecar format, so symbols can be saved and parsed back */
+impl fmt::Display for Symbols {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (addr, s) in &self.entries {
+            let prefix = if s.code { "pc " } else { "" };
+            write!(f, "{}{}:", prefix, addr)?;
+            if let Some(name) = &s.name {
+                write!(f, " {}", name)?;
+            }
+            if let Some(comment) = &s.comment {
+                write!(f, " ; {}", comment)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_and_write() {
+        let symbols = Symbols::parse(
+            "# Day 25
+             0x1f4: room_table
+             pc 1050: print_string ; prints the string at [rb+1]
+             1062: ; just a comment",
+        )
+        .unwrap();
+
+        assert_eq!(symbols.name(500), Some("room_table"));
+        assert_eq!(symbols.label(500), None);
+        assert_eq!(symbols.label(1050), Some("print_string"));
+        assert_eq!(symbols.comment(1050), Some("prints the string at [rb+1]"));
+        assert_eq!(symbols.name(1062), None);
+        assert_eq!(symbols.comment(1062), Some("just a comment"));
+        assert_eq!(symbols.resolve("print_string"), Some(1050));
+        assert_eq!(symbols.resolve("0x10"), Some(16));
+        assert_eq!(symbols.resolve("nothing"), None);
+
+        assert_eq!(Symbols::parse(&symbols.to_string()).unwrap(), symbols);
+    }
+
+    #[test]
+    fn unnamed_code() {
+        let mut symbols = Symbols::new();
+        symbols.set_comment(5, "comment");
+        symbols.entries.get_mut(&5).unwrap().code = true;
+        assert_eq!(symbols.to_string(), "pc 5: ; comment\n");
+
+        let parsed = Symbols::parse(&symbols.to_string()).unwrap();
+        assert_eq!(parsed, symbols);
+        assert!(parsed.get(5).unwrap().code);
+        assert_eq!(Symbols::parse("pc 7:").unwrap().to_string(), "pc 7:\n");
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!(Symbols::parse("room_table").unwrap_err().contains("line 1"));
+        assert!(Symbols::parse("x: a").unwrap_err().contains("invalid address"));
+        assert!(Symbols::parse("1: two words").unwrap_err().contains("invalid name"));
+        assert!(Symbols::parse("1: a\n2: a").unwrap_err().contains("line 2: 'a' is already"));
+    }
+}
@@ -0,0 +1,411 @@
+/*!
+ * Static checks of an intcode program, made without running it.
+ *
+ * Instructions are followed from the starting pc and from any code labels in
+ * the program's symbols, through fall through and jumps to immediate targets.
+ * Jumps through memory can't be followed, except for the call pattern where
+ * the address after an unconditional jump is stored as a constant just before
+ * it, which is taken to be a return address.
+ *
+ * Reachable instructions which the program itself writes to can't be known
+ * ahead of time. The walk is repeated until no new such writes are found, with
+ * patched operands treated as unknown and patched opcodes not followed at all.
+ * Words of the program which are never written to are constants, so a jump
+ * conditioned on one is known to be taken or not. Writes relative to the base
+ * are assumed to go to the stack rather than into the program.
+ */
+
+use super::symbols::Symbols;
+use super::opcodes::{Access, Builtin};
+use super::{IntCodeMachine, Ins, Parameter, Word};
+
+use std::collections::HashSet;
+use std::fmt;
+use std::ops::Range;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Problem {
+    UnknownOpcode { addr: usize, opcode: String },
+    InvalidMode { addr: usize, opcode: String },
+    /** An instruction whose output parameter is in immediate mode */
+    ImmediateWrite { addr: usize, ins: String },
+    /** An instruction which extends past the end of the program */
+    Truncated { addr: usize, ins: String },
+    JumpOutOfRange { addr: usize, ins: String },
+    /** Execution continues past the last word of the program */
+    FallsOffEnd { addr: usize },
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Problem::UnknownOpcode { addr, opcode } => write!(f, "{}: unknown opcode {}", addr, opcode),
+            Problem::InvalidMode { addr, opcode } => write!(f, "{}: invalid parameter mode in {}", addr, opcode),
+            Problem::ImmediateWrite { addr, ins } => write!(f, "{}: writes to an immediate parameter: {}", addr, ins),
+            Problem::Truncated { addr, ins } => write!(f, "{}: runs past the end of the program: {}", addr, ins),
+            Problem::JumpOutOfRange { addr, ins } => write!(f, "{}: jumps outside the program: {}", addr, ins),
+            Problem::FallsOffEnd { addr } => write!(f, "{}: execution runs off the end of the program", addr),
+        }
+    }
+}
+
+pub struct Report {
+    pub problems: Vec<Problem>,
+    /** Addresses of jumps whose targets are read from memory */
+    pub indirect_jumps: Vec<usize>,
+    /** Address ranges not part of any reachable instruction, usually data */
+    pub unreachable: Vec<Range<usize>>,
+    /** Reachable instructions whose opcode the program overwrites, which aren't followed */
+    pub self_modifying: Vec<usize>,
+}
+
+impl Report {
+    pub fn is_ok(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for p in &self.problems {
+            writeln!(f, "error: {}", p)?;
+        }
+        for addr in &self.self_modifying {
+            writeln!(f, "warning: {}: opcode is overwritten at runtime, not followed", addr)?;
+        }
+        for r in &self.unreachable {
+            writeln!(f, "unreachable: {}..{} ({} words)", r.start, r.end, r.len())?;
+        }
+        write!(
+            f,
+            "{} problems, {} indirect jumps, {} unreachable words",
+            self.problems.len(),
+            self.indirect_jumps.len(),
+            self.unreachable.iter().map(|r| r.len()).sum::<usize>()
+        )
+    }
+}
+
+/** The value of a parameter known without running the program */
+fn constant<W: Word>(p: &Parameter<W>) -> Option<&W> {
+    match p {
+        Parameter::Direct(v) => Some(v),
+        _ => None,
+    }
+}
+
+/** The result stored by an instruction whose inputs are all immediate */
+fn constant_result<W: Word>(ins: &Ins<W>) -> Option<W> {
+    let (a, b) = match &ins.params[..] {
+        [a, b, _] => (constant(a)?, constant(b)?),
+        _ => return None,
+    };
+    match ins.builtin()? {
+        Builtin::Add => a.checked_add(b),
+        Builtin::Mul => a.checked_mul(b),
+        Builtin::LessThan => Some(if a < b { W::one() } else { W::zero() }),
+        Builtin::Equals => Some(if a == b { W::one() } else { W::zero() }),
+        _ => None,
+    }
+}
+
+fn output_param<W>(ins: &Ins<W>) -> Option<&Parameter<W>> {
+    let i = ins.op.params().iter().position(|a| *a == Access::Write)?;
+    Some(&ins.params[i])
+}
+
+/**
+ * Result of following the program once, assuming `patched` addresses are
+ * overwritten and any word of the program not in `written` is not
+ */
+struct Walk {
+    reached: Vec<bool>,
+    problems: Vec<Problem>,
+    indirect_jumps: Vec<usize>,
+    self_modifying: Vec<usize>,
+    /** Fixed addresses written by reachable instructions */
+    writes: HashSet<usize>,
+}
+
+fn walk<W: Word>(
+    machine: &IntCodeMachine<W>,
+    starts: &[usize],
+    patched: &HashSet<usize>,
+    written: &HashSet<usize>,
+) -> Walk {
+    let len = machine.memory().len();
+    let mut w = Walk {
+        reached: vec![false; len],
+        problems: Vec::new(),
+        indirect_jumps: Vec::new(),
+        self_modifying: Vec::new(),
+        writes: HashSet::new(),
+    };
+    let mut visited = HashSet::new();
+    let mut work = starts.to_vec();
+
+    while let Some(start) = work.pop() {
+        // Constants stored so far in this block, which may be return addresses
+        let mut stored: Vec<W> = Vec::new();
+        // The program runs unmodified from the entry point until its first write
+        let mut original = work.is_empty() && start == starts[0];
+        let mut addr = start;
+        loop {
+            if addr >= len {
+                w.problems.push(Problem::FallsOffEnd { addr });
+                break;
+            }
+            if !visited.insert(addr) {
+                break;
+            }
+            w.reached[addr] = true;
+            if patched.contains(&addr) && !original {
+                w.self_modifying.push(addr);
+                break;
+            }
+
+            let opcode = machine.load(addr);
+            if !machine.opcodes().contains(&opcode) {
+                w.problems.push(Problem::UnknownOpcode {
+                    addr,
+                    opcode: opcode.to_string(),
+                });
+                break;
+            }
+            let (ins, n) = match machine.decode(addr) {
+                Some(d) => d,
+                None => {
+                    w.problems.push(Problem::InvalidMode {
+                        addr,
+                        opcode: opcode.to_string(),
+                    });
+                    break;
+                }
+            };
+
+            for r in w.reached.iter_mut().take((addr + n).min(len)).skip(addr) {
+                *r = true;
+            }
+            if addr + n > len {
+                w.problems.push(Problem::Truncated {
+                    addr,
+                    ins: ins.to_string(),
+                });
+            }
+            match output_param(&ins) {
+                Some(Parameter::Direct(_)) => w.problems.push(Problem::ImmediateWrite {
+                    addr,
+                    ins: ins.to_string(),
+                }),
+                Some(Parameter::Position(p)) => w.writes.extend(p.to_usize()),
+                _ => {}
+            }
+
+            // Results computed from operands overwritten at runtime aren't constants
+            let fixed = original || !(addr + 1..addr + n).any(|a| patched.contains(&a));
+            if let Some(Parameter::Position(_)) = output_param(&ins) {
+                original = false;
+            }
+            if let Some(v) = constant_result(&ins).filter(|_| fixed) {
+                stored.push(v);
+            }
+            // The value of the i'th operand, if it isn't overwritten at runtime
+            let known = |i: usize, p: &Parameter<W>| match p {
+                _ if !original && patched.contains(&(addr + 1 + i)) => None,
+                Parameter::Position(a) => a
+                    .to_usize()
+                    .filter(|a| *a < len && !written.contains(a))
+                    .map(|a| machine.load(a)),
+                p => constant(p).cloned(),
+            };
+
+            let (cond, target, jump_if) = match (ins.builtin(), &ins.params[..]) {
+                (Some(Builtin::Halt), _) => break,
+                (Some(Builtin::JumpNz), [c, t]) => (c, t, true),
+                (Some(Builtin::JumpEz), [c, t]) => (c, t, false),
+                _ => {
+                    addr += n;
+                    continue;
+                }
+            };
+
+            // Whether the jump is always or never taken, if the condition is known
+            let taken = known(0, cond).map(|c| c.is_zero() != jump_if);
+            if taken != Some(false) {
+                match known(1, target).map(|t| t.to_usize().filter(|t| *t < len)) {
+                    Some(Some(t)) => work.push(t),
+                    Some(None) => w.problems.push(Problem::JumpOutOfRange {
+                        addr,
+                        ins: ins.to_string(),
+                    }),
+                    None => w.indirect_jumps.push(addr),
+                }
+            }
+            if taken == Some(true) {
+                // A call returns to the address after the jump
+                if stored.contains(&W::from((addr + n) as i64)) {
+                    work.push(addr + n);
+                }
+                break;
+            }
+            addr += n;
+        }
+    }
+
+    w
+}
+
+/** Check every instruction reachable from the machine's pc or a code label in `symbols` */
+pub fn validate<W: Word>(machine: &IntCodeMachine<W>, symbols: &Symbols) -> Report {
+    let mut starts = vec![machine.pc()];
+    starts.extend(symbols.iter().filter(|(_, s)| s.code).map(|(a, _)| a));
+
+    // Both sets only grow as more of the program is found to be reachable
+    let mut patched = HashSet::new();
+    let mut written = HashSet::new();
+    let mut w = loop {
+        let w = walk(machine, &starts, &patched, &written);
+        let before = (patched.len(), written.len());
+        patched.extend(w.writes.iter().filter(|a| w.reached.get(**a) == Some(&true)));
+        written.extend(w.writes.iter().cloned());
+        if (patched.len(), written.len()) == before {
+            break w;
+        }
+    };
+
+    let len = w.reached.len();
+    let mut unreachable = Vec::new();
+    let mut addr = 0;
+    while addr < len {
+        if w.reached[addr] {
+            addr += 1;
+            continue;
+        }
+        let start = addr;
+        while addr < len && !w.reached[addr] {
+            addr += 1;
+        }
+        unreachable.push(start..addr);
+    }
+
+    w.problems.sort_by_key(|p| match p {
+        Problem::UnknownOpcode { addr, .. }
+        | Problem::InvalidMode { addr, .. }
+        | Problem::ImmediateWrite { addr, .. }
+        | Problem::Truncated { addr, .. }
+        | Problem::JumpOutOfRange { addr, .. }
+        | Problem::FallsOffEnd { addr } => *addr,
+    });
+    w.problems.dedup();
+    w.indirect_jumps.sort();
+    w.self_modifying.sort();
+
+    Report {
+        problems: w.problems,
+        indirect_jumps: w.indirect_jumps,
+        unreachable,
+        self_modifying: w.self_modifying,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn check(program: &str) -> Report {
+        validate(&IntCodeMachine::load_file(program.to_string()), &Symbols::new())
+    }
+
+    #[test]
+    fn clean() {
+        let report = check("109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99");
+        assert!(report.is_ok());
+        assert!(report.unreachable.is_empty());
+        assert!(report.indirect_jumps.is_empty());
+
+        // Day 5's "is the input 8" program, with its data words after the halt
+        let report = check("3,9,8,9,10,9,4,9,99,-1,8");
+        assert!(report.is_ok());
+        assert_eq!(report.unreachable, vec![9..11]);
+    }
+
+    #[test]
+    fn problems() {
+        assert_eq!(check("104,1,42").problems, vec![Problem::UnknownOpcode {
+            addr: 2,
+            opcode: "42".to_string()
+        }]);
+        assert_eq!(check("304,1,99").problems, vec![Problem::InvalidMode {
+            addr: 0,
+            opcode: "304".to_string()
+        }]);
+        assert_eq!(check("11101,1,1,5,99,0").problems, vec![Problem::ImmediateWrite {
+            addr: 0,
+            ins: "ADD 1, 1, 5".to_string()
+        }]);
+        assert_eq!(check("1105,1,50").problems, vec![Problem::JumpOutOfRange {
+            addr: 0,
+            ins: "JNZ 1, 50".to_string()
+        }]);
+        assert_eq!(check("104,1").problems, vec![Problem::FallsOffEnd { addr: 2 }]);
+        assert_eq!(check("104,1,4").problems, vec![
+            Problem::Truncated {
+                addr: 2,
+                ins: "OUT [0]".to_string()
+            },
+            Problem::FallsOffEnd { addr: 4 },
+        ]);
+    }
+
+    #[test]
+    fn unconditional_jumps() {
+        // Skips over a data word, then never falls through the JZ 0
+        let report = check("1105,1,4,77,1106,0,8,88,99");
+        assert!(report.is_ok());
+        assert_eq!(report.unreachable, vec![3..4, 7..8]);
+
+        // The opcode at 0 is never written, so the jump is never taken
+        let report = check("1006,0,6,104,1,99,77");
+        assert!(report.is_ok());
+        assert_eq!(report.unreachable, vec![6..7]);
+    }
+
+    #[test]
+    fn calls() {
+        // main: push the return address 7 and call the function at 10, which
+        // outputs then returns through the stored address
+        let program = "21101,0,7,0,1105,1,10,104,2,99,104,1,2106,0,0";
+        let report = check(program);
+        assert!(report.is_ok());
+        assert_eq!(report.indirect_jumps, vec![12]);
+        assert!(report.unreachable.is_empty());
+
+        // Without the stored return address the code after the call is unreachable
+        let report = check("21101,0,5,0,1105,1,10,104,2,99,104,1,2106,0,0");
+        assert_eq!(report.unreachable, vec![7..10]);
+
+        // Unless a label says otherwise
+        let symbols = Symbols::parse("pc 7: after_call").unwrap();
+        let m = IntCodeMachine::load_file("21101,0,5,0,1105,1,10,104,2,99,104,1,2106,0,0".to_string());
+        assert!(validate(&m, &symbols).unreachable.is_empty());
+    }
+
+    #[test]
+    fn self_modifying() {
+        // Like day 5: the first instruction turns the 1100 at 4 into a 1101
+        let report = check("1101,1,1100,4,1100,1,1,9,99,0");
+        assert!(report.is_ok());
+        assert_eq!(report.self_modifying, vec![4]);
+
+        // Patching the target of a jump makes it indirect
+        let report = check("1101,0,9,6,1105,1,0,99,99");
+        assert!(report.is_ok());
+        assert_eq!(report.indirect_jumps, vec![4]);
+        assert_eq!(report.unreachable, vec![7..9]);
+
+        // Code which runs before the patch is decoded as it is in the file
+        let report = check("1101,1,1,0,99");
+        assert!(report.is_ok());
+        assert!(report.self_modifying.is_empty());
+    }
+}
@@ -1,3 +1,8 @@
 pub mod array2d;
+pub mod cycle;
+pub mod math;
+pub mod search;
+pub mod sparse_grid;
+pub mod vec;
 pub mod vec2;
 pub mod intcode;
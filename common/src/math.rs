@@ -0,0 +1,223 @@
+/*!
+ * Number theory on machine integers, for puzzles which would otherwise need
+ * BigInt just to multiply two numbers below a large modulus.
+ */
+
+use num::Integer;
+
+/** An integer type whose products can be reduced by a modulus without overflowing */
+pub trait ModInt: Integer + Copy {
+    /** `self * other` mod `m`, in `0..m` */
+    fn mul_mod(self, other: Self, m: Self) -> Self;
+}
+
+impl ModInt for u64 {
+    fn mul_mod(self, other: Self, m: Self) -> Self {
+        ((self as u128 * other as u128) % m as u128) as u64
+    }
+}
+
+/** Works for any positive modulus, including negative operands */
+impl ModInt for i128 {
+    fn mul_mod(self, other: Self, m: Self) -> Self {
+        assert!(m > 0, "modulus {} is not positive", m);
+        let m = m as u128;
+        let mut a = self.rem_euclid(m as i128) as u128;
+        let mut b = other.rem_euclid(m as i128) as u128;
+
+        // Double and add, as a * b may not fit in 128 bits. Each sum is of two
+        // values below m < 2^127, so can't overflow.
+        let mut out = 0;
+        while b > 0 {
+            if b & 1 == 1 {
+                out = (out + a) % m;
+            }
+            a = (a + a) % m;
+            b >>= 1;
+        }
+        out as i128
+    }
+}
+
+pub fn mod_mul<T: ModInt>(a: T, b: T, m: T) -> T {
+    a.mul_mod(b, m)
+}
+
+/** `base` to the power `exp`, mod `m` */
+pub fn mod_pow<T: ModInt>(base: T, exp: u64, m: T) -> T {
+    let mut out = T::one().mul_mod(T::one(), m);
+    let mut base = base.mul_mod(T::one(), m);
+    let mut exp = exp;
+    while exp > 0 {
+        if exp & 1 == 1 {
+            out = out.mul_mod(base, m);
+        }
+        base = base.mul_mod(base, m);
+        exp >>= 1;
+    }
+    out
+}
+
+/** The gcd of `a` and `b`, with `x` and `y` such that `a * x + b * y = gcd` */
+pub fn egcd(a: i128, b: i128) -> (i128, i128, i128) {
+    let (mut r0, mut r1) = (a, b);
+    let (mut x0, mut x1) = (1, 0);
+    let (mut y0, mut y1) = (0, 1);
+    while r1 != 0 {
+        let q = r0.div_euclid(r1);
+        (r0, r1) = (r1, r0 - q * r1);
+        (x0, x1) = (x1, x0 - q * x1);
+        (y0, y1) = (y1, y0 - q * y1);
+    }
+    if r0 < 0 {
+        (-r0, -x0, -y0)
+    } else {
+        (r0, x0, y0)
+    }
+}
+
+/** The `x` in `0..m` with `a * x = 1` mod `m`, if `a` and `m` are coprime */
+pub fn mod_inverse(a: i128, m: i128) -> Option<i128> {
+    let (g, x, _) = egcd(a.rem_euclid(m), m);
+    if g == 1 {
+        Some(x.rem_euclid(m))
+    } else {
+        None
+    }
+}
+
+/**
+ * Solve `x = r` mod `m` for every `(r, m)` given, returning `x` and the modulus
+ * of the solution (the lcm of the `m`s). The moduli needn't be coprime, but if
+ * the congruences contradict each other there's no solution.
+ */
+pub fn crt<I: IntoIterator<Item = (i128, i128)>>(congruences: I) -> Option<(i128, i128)> {
+    let mut x = 0;
+    let mut m = 1;
+    for (r2, m2) in congruences {
+        let (g, _, _) = egcd(m, m2);
+        let diff = r2 - x;
+        if diff % g != 0 {
+            return None;
+        }
+
+        // x + m * k = r2 (mod m2), so k = diff / g * (m / g)^-1 (mod m2 / g)
+        let step = m2 / g;
+        let k = mod_mul(diff / g, mod_inverse(m / g, step)?, step);
+        let lcm = m / g * m2;
+        x = (x + mod_mul(m, k, lcm)).rem_euclid(lcm);
+        m = lcm;
+    }
+    Some((x, m))
+}
+
+/** The lcm of all the values, which is 1 if there are none */
+pub fn lcm<T: Integer, I: IntoIterator<Item = T>>(values: I) -> T {
+    values.into_iter().fold(T::one(), |l, v| l.lcm(&v))
+}
+
+/**
+ * The first value in `lo..hi` for which `pred` holds, where `pred` is false up
+ * to some point and true after it. `None` if it doesn't hold anywhere.
+ */
+pub fn binary_search<T: Integer + Copy, F: FnMut(T) -> bool>(lo: T, hi: T, mut pred: F) -> Option<T> {
+    let two = T::one() + T::one();
+    let (mut lo, mut hi) = (lo, hi);
+    // Everything below lo is false, and everything from hi up true
+    let end = hi;
+    while lo < hi {
+        // The floor of the mean, without forming hi - lo, which can overflow
+        let mid = lo.div_floor(&two) + hi.div_floor(&two) + (lo.mod_floor(&two) + hi.mod_floor(&two)) / two;
+        if pred(mid) {
+            hi = mid;
+        } else {
+            lo = mid + T::one();
+        }
+    }
+    if lo < end {
+        Some(lo)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn modular() {
+        let m: u64 = 119315717514047;
+        assert_eq!(mod_mul(m - 1, m - 1, m), 1);
+        assert_eq!(mod_mul(u64::MAX, u64::MAX, 1_000_000_007), ((u64::MAX % 1_000_000_007) as u128).pow(2) as u64 % 1_000_000_007);
+        assert_eq!(mod_pow(2u64, 10, 1000), 24);
+        assert_eq!(mod_pow(5u64, 0, 1), 0);
+        // Fermat's little theorem
+        assert_eq!(mod_pow(12345u64, 1_000_000_006, 1_000_000_007), 1);
+
+        let big = (1i128 << 126) + 1;
+        assert_eq!(mod_mul(big - 1, big - 1, big), 1);
+        assert_eq!(mod_mul(-3i128, 4, 10), 8);
+        assert_eq!(mod_pow(-2i128, 3, 7), 6);
+        assert_eq!(mod_pow(3i128, 200, big), {
+            let half = mod_pow(3i128, 100, big);
+            mod_mul(half, half, big)
+        });
+    }
+
+    #[test]
+    fn gcd_and_inverse() {
+        for (a, b) in [(240, 46), (46, 240), (-12, 18), (7, 0), (0, 0), (17, 5)].iter().cloned() {
+            let (g, x, y) = egcd(a, b);
+            assert_eq!(g, (a as i64).gcd(&(b as i64)) as i128, "gcd({}, {})", a, b);
+            assert_eq!(a * x + b * y, g);
+        }
+
+        assert_eq!(mod_inverse(3, 10), Some(7));
+        assert_eq!(mod_inverse(-3, 10), Some(3));
+        assert_eq!(mod_inverse(4, 10), None);
+        for a in 1..97 {
+            assert_eq!(mod_mul(a, mod_inverse(a, 97).unwrap(), 97), 1);
+        }
+    }
+
+    #[test]
+    fn chinese_remainder() {
+        assert_eq!(crt(vec![(2, 3), (3, 5), (2, 7)]), Some((23, 105)));
+        // Moduli with common factors
+        assert_eq!(crt(vec![(1, 4), (3, 6)]), Some((9, 12)));
+        assert_eq!(crt(vec![(1, 4), (2, 6)]), None);
+        assert_eq!(crt(vec![(-1, 5)]), Some((4, 5)));
+        assert_eq!(crt(vec![]), Some((0, 1)));
+
+        // Large coprime moduli whose product needs the wide multiply
+        let (p, q) = (1_000_000_007i128, 998_244_353i128);
+        let x = 123_456_789_012_345_678i128;
+        assert_eq!(crt(vec![(x % p, p), (x % q, q)]), Some((x % (p * q), p * q)));
+    }
+
+    #[test]
+    fn lcm_and_search() {
+        assert_eq!(lcm(vec![4, 6, 10]), 60);
+        assert_eq!(lcm(vec![18u64, 28, 44]), 2772);
+        assert_eq!(lcm(Vec::<i32>::new()), 1);
+
+        assert_eq!(binary_search(0, 100, |x| x * x >= 50), Some(8));
+        assert_eq!(binary_search(0, 100, |_| true), Some(0));
+        assert_eq!(binary_search(0, 100, |_| false), None);
+        assert_eq!(binary_search(-50i64, 50, |x| x > -7), Some(-6));
+        assert_eq!(binary_search(5, 5, |_| true), None);
+        assert_eq!(binary_search(i64::MIN, i64::MAX, |x| x >= -3), Some(-3));
+        assert_eq!(binary_search(i64::MIN, i64::MAX, |_| true), Some(i64::MIN));
+        assert_eq!(binary_search(i64::MIN, i64::MAX, |x| x == i64::MAX - 1), Some(i64::MAX - 1));
+        assert_eq!(binary_search(i8::MIN, i8::MAX, |_| false), None);
+        assert_eq!(binary_search(u8::MAX - 1, u8::MAX, |_| true), Some(u8::MAX - 1));
+
+        let mut calls = 0;
+        binary_search(0u64, 1 << 40, |x| {
+            calls += 1;
+            x >= 1_000_000_000
+        });
+        assert!(calls <= 41);
+    }
+}
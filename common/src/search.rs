@@ -0,0 +1,300 @@
+/*!
+ * Shortest path searches over any state type. Each search takes the start
+ * state and a closure giving the states reachable from a state (with the cost
+ * of getting to each one, for the weighted searches).
+ */
+
+use crate::array2d::{Array2D, Neighbourhood};
+use crate::vec2::Vec2u;
+
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap, VecDeque};
+use std::hash::Hash;
+use std::ops::Add;
+
+/** The result of a search: every state reached, and how to get there */
+#[derive(Clone, Debug)]
+pub struct Search<S, C> {
+    pub start: S,
+    /** The cost of the cheapest path from the start to each state */
+    pub distances: HashMap<S, C>,
+    /** The state before each state on its cheapest path. The start has none. */
+    pub predecessors: HashMap<S, S>,
+}
+
+impl<S: Hash + Eq + Clone, C: Copy> Search<S, C> {
+    fn new(start: S, zero: C) -> Self {
+        let mut distances = HashMap::new();
+        distances.insert(start.clone(), zero);
+        Self {
+            start,
+            distances,
+            predecessors: HashMap::new(),
+        }
+    }
+
+    pub fn distance(&self, state: &S) -> Option<C> {
+        self.distances.get(state).copied()
+    }
+
+    /** The states on the cheapest path from the start to `goal`, including both */
+    pub fn path_to(&self, goal: &S) -> Option<Vec<S>> {
+        if !self.distances.contains_key(goal) {
+            return None;
+        }
+        let mut path = vec![goal.clone()];
+        while let Some(prev) = self.predecessors.get(path.last().unwrap()) {
+            path.push(prev.clone());
+        }
+        path.reverse();
+        Some(path)
+    }
+}
+
+/** Visit every state reachable from `start`, where each move costs 1 */
+pub fn bfs<S, N, I>(start: S, mut neighbours: N) -> Search<S, usize>
+where
+    S: Hash + Eq + Clone,
+    N: FnMut(&S) -> I,
+    I: IntoIterator<Item = S>,
+{
+    let mut search = Search::new(start.clone(), 0);
+    let mut queue = VecDeque::new();
+    queue.push_back(start);
+
+    while let Some(state) = queue.pop_front() {
+        let next_dist = search.distances[&state] + 1;
+        for next in neighbours(&state) {
+            if !search.distances.contains_key(&next) {
+                search.distances.insert(next.clone(), next_dist);
+                search.predecessors.insert(next.clone(), state.clone());
+                queue.push_back(next);
+            }
+        }
+    }
+
+    search
+}
+
+/** A state waiting in a priority queue, popped cheapest `priority` first */
+struct Queued<S, C> {
+    priority: C,
+    cost: C,
+    state: S,
+}
+
+impl<S, C: Ord> PartialEq for Queued<S, C> {
+    fn eq(&self, other: &Self) -> bool {
+        self.priority == other.priority
+    }
+}
+
+impl<S, C: Ord> Eq for Queued<S, C> {}
+
+impl<S, C: Ord> PartialOrd for Queued<S, C> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<S, C: Ord> Ord for Queued<S, C> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Reversed, as BinaryHeap pops the greatest
+        other.priority.cmp(&self.priority)
+    }
+}
+
+/**
+ * Visit every state reachable from `start`, where `neighbours` gives each next
+ * state with the (non-negative) cost of moving to it
+ */
+pub fn dijkstra<S, C, N, I>(start: S, mut neighbours: N) -> Search<S, C>
+where
+    S: Hash + Eq + Clone,
+    C: Ord + Copy + Add<Output = C> + Default,
+    N: FnMut(&S) -> I,
+    I: IntoIterator<Item = (S, C)>,
+{
+    let mut search = Search::new(start.clone(), C::default());
+    let mut queue = BinaryHeap::new();
+    queue.push(Queued { priority: C::default(), cost: C::default(), state: start });
+
+    while let Some(Queued { cost, state, .. }) = queue.pop() {
+        if cost > search.distances[&state] {
+            // Already reached more cheaply since this was queued
+            continue;
+        }
+        for (next, step) in neighbours(&state) {
+            let next_cost = cost + step;
+            if search.distance(&next).is_none_or(|d| next_cost < d) {
+                search.distances.insert(next.clone(), next_cost);
+                search.predecessors.insert(next.clone(), state.clone());
+                queue.push(Queued { priority: next_cost, cost: next_cost, state: next });
+            }
+        }
+    }
+
+    search
+}
+
+/**
+ * The cheapest path from `start` to any state satisfying `is_goal`, with its
+ * cost. `heuristic` estimates the remaining cost from a state, and must never
+ * overestimate it for the path to be the cheapest.
+ */
+pub fn astar<S, C, N, I, H, G>(start: S, mut neighbours: N, mut heuristic: H, mut is_goal: G) -> Option<(C, Vec<S>)>
+where
+    S: Hash + Eq + Clone,
+    C: Ord + Copy + Add<Output = C> + Default,
+    N: FnMut(&S) -> I,
+    I: IntoIterator<Item = (S, C)>,
+    H: FnMut(&S) -> C,
+    G: FnMut(&S) -> bool,
+{
+    let mut search = Search::new(start.clone(), C::default());
+    let mut queue = BinaryHeap::new();
+    queue.push(Queued { priority: heuristic(&start), cost: C::default(), state: start });
+
+    while let Some(Queued { cost, state, .. }) = queue.pop() {
+        if cost > search.distances[&state] {
+            continue;
+        }
+        if is_goal(&state) {
+            return Some((cost, search.path_to(&state).unwrap()));
+        }
+        for (next, step) in neighbours(&state) {
+            let next_cost = cost + step;
+            if search.distance(&next).is_none_or(|d| next_cost < d) {
+                search.distances.insert(next.clone(), next_cost);
+                search.predecessors.insert(next.clone(), state.clone());
+                let priority = next_cost + heuristic(&next);
+                queue.push(Queued { priority, cost: next_cost, state: next });
+            }
+        }
+    }
+
+    None
+}
+
+/**
+ * Breadth first search of a grid, moving up, down, left or right onto cells
+ * for which `passable` is true
+ */
+pub fn grid_bfs<T, P>(grid: &Array2D<T>, start: &Vec2u, passable: P) -> Search<Vec2u, usize>
+where
+    P: Fn(&T) -> bool,
+{
+    bfs(start.clone(), |p| {
+        grid.neighbour_cells(p, Neighbourhood::FOUR)
+            .filter(|(_, c)| passable(c))
+            .map(|(q, _)| q)
+            .collect::<Vec<_>>()
+    })
+}
+
+/** The shortest path between two cells of a grid, moving as for `grid_bfs` */
+pub fn grid_astar<T, P>(grid: &Array2D<T>, start: &Vec2u, goal: &Vec2u, passable: P) -> Option<(usize, Vec<Vec2u>)>
+where
+    P: Fn(&T) -> bool,
+{
+    astar(
+        start.clone(),
+        |p| {
+            grid.neighbour_cells(p, Neighbourhood::FOUR)
+                .filter(|(_, c)| passable(c))
+                .map(|(q, _)| (q, 1))
+                .collect::<Vec<_>>()
+        },
+        |p| manhattan(p, goal),
+        |p| p == goal,
+    )
+}
+
+fn manhattan(a: &Vec2u, b: &Vec2u) -> usize {
+    a.x.abs_diff(b.x) + a.y.abs_diff(b.y)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::array2d::FromGridChar;
+
+    #[derive(PartialEq)]
+    enum Cell {
+        Open,
+        Wall,
+    }
+
+    impl FromGridChar for Cell {
+        fn from_grid_char(c: char) -> Option<Self> {
+            match c {
+                '.' => Some(Cell::Open),
+                '#' => Some(Cell::Wall),
+                _ => None,
+            }
+        }
+    }
+
+    fn maze() -> Array2D<Cell> {
+        Array2D::parse_trimmed(
+            "
+            .....
+            ###.#
+            .#...
+            #..#.
+        ",
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn grid() {
+        let maze = maze();
+        let open = |c: &Cell| *c == Cell::Open;
+        let at = |x, y| Vec2u::new(x, y);
+
+        let search = grid_bfs(&maze, &at(0, 0), open);
+        assert_eq!(search.distance(&at(4, 3)), Some(7));
+        assert_eq!(search.distance(&at(1, 3)), Some(8));
+        // Open, but walled off
+        assert_eq!(search.distance(&at(0, 2)), None);
+        assert_eq!(search.path_to(&at(0, 0)), Some(vec![at(0, 0)]));
+        assert_eq!(search.path_to(&at(3, 1)).unwrap(), vec![at(0, 0), at(1, 0), at(2, 0), at(3, 0), at(3, 1)]);
+
+        let (len, path) = grid_astar(&maze, &at(0, 0), &at(1, 3), open).unwrap();
+        assert_eq!(len, 8);
+        assert_eq!(path.len(), 9);
+        assert_eq!(path.last(), Some(&at(1, 3)));
+        assert!(path.windows(2).all(|w| manhattan(&w[0], &w[1]) == 1 && open(&maze[&w[1]])));
+
+        assert_eq!(grid_astar(&maze, &at(0, 0), &at(4, 0), |_| true).unwrap().0, 4);
+        assert!(grid_astar(&maze, &at(0, 0), &at(0, 2), open).is_none());
+    }
+
+    #[test]
+    fn weighted() {
+        // 0 -> 1 directly costs 10, but 0 -> 2 -> 3 -> 1 costs 6
+        let edges = |n: &u32| -> Vec<(u32, u32)> {
+            match n {
+                0 => vec![(1, 10), (2, 1)],
+                2 => vec![(3, 2)],
+                3 => vec![(1, 3), (4, 20)],
+                1 => vec![(4, 1)],
+                _ => vec![],
+            }
+        };
+
+        let search = dijkstra(0, edges);
+        assert_eq!(search.distance(&1), Some(6));
+        assert_eq!(search.distance(&4), Some(7));
+        assert_eq!(search.path_to(&4), Some(vec![0, 2, 3, 1, 4]));
+
+        assert_eq!(astar(0, edges, |_| 0, |n| *n == 4), Some((7, vec![0, 2, 3, 1, 4])));
+        assert_eq!(astar(0, edges, |_| 0, |n| *n == 5), None);
+
+        // Counting up to 10 from 1 by adding one or doubling
+        let steps = bfs(1u32, |n| vec![n + 1, n * 2].into_iter().filter(|m| *m <= 10));
+        assert_eq!(steps.distance(&10), Some(4));
+        assert_eq!(steps.path_to(&10), Some(vec![1, 2, 4, 5, 10]));
+    }
+}
@@ -0,0 +1,202 @@
+use crate::array2d::Array2D;
+use crate::vec2::Vec2i;
+
+use std::collections::HashMap;
+
+/** Which way y increases when a grid is drawn */
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum YAxis {
+    /** Row y + 1 is drawn below row y, as on a screen */
+    Down,
+    /** Row y + 1 is drawn above row y, as on a graph */
+    Up
+}
+
+/**
+ * A grid without fixed edges, storing only the cells which have been set. Any
+ * coordinate can be used, including negative ones.
+ */
+#[derive(Clone, PartialEq, Debug)]
+pub struct SparseGrid<T> {
+    cells: HashMap<Vec2i, T>,
+    /** The smallest and largest x and y of any cell set, if there are any */
+    bounds: Option<(Vec2i, Vec2i)>
+}
+
+impl <T> Default for SparseGrid<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl <T> SparseGrid<T> {
+    pub fn new() -> Self {
+        Self {
+            cells: HashMap::new(),
+            bounds: None
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.cells.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+
+    pub fn contains(&self, p: &Vec2i) -> bool {
+        self.cells.contains_key(p)
+    }
+
+    pub fn get(&self, p: &Vec2i) -> Option<&T> {
+        self.cells.get(p)
+    }
+
+    pub fn get_mut(&mut self, p: &Vec2i) -> Option<&mut T> {
+        self.cells.get_mut(p)
+    }
+
+    /** Set a cell, returning what was there before */
+    pub fn set(&mut self, p: Vec2i, value: T) -> Option<T> {
+        self.extend_bounds(&p);
+        self.cells.insert(p, value)
+    }
+
+    /** The cell at `p`, setting it to `default` first if it isn't set */
+    pub fn get_or_insert(&mut self, p: Vec2i, default: T) -> &mut T {
+        self.extend_bounds(&p);
+        self.cells.entry(p).or_insert(default)
+    }
+
+    pub fn remove(&mut self, p: &Vec2i) -> Option<T> {
+        let old = self.cells.remove(p)?;
+        if let Some((min, max)) = &self.bounds {
+            if p.x == min.x || p.x == max.x || p.y == min.y || p.y == max.y {
+                // The edge may have moved in
+                self.bounds = None;
+                let keys: Vec<Vec2i> = self.cells.keys().cloned().collect();
+                for k in &keys {
+                    self.extend_bounds(k);
+                }
+            }
+        }
+        Some(old)
+    }
+
+    fn extend_bounds(&mut self, p: &Vec2i) {
+        self.bounds = Some(match self.bounds.take() {
+            None => (p.clone(), p.clone()),
+            Some((min, max)) => (
+                Vec2i::new(min.x.min(p.x), min.y.min(p.y)),
+                Vec2i::new(max.x.max(p.x), max.y.max(p.y))
+            )
+        });
+    }
+
+    /** The smallest and largest corners of a box around every cell set */
+    pub fn bounds(&self) -> Option<(Vec2i, Vec2i)> {
+        self.bounds.clone()
+    }
+
+    /** Every cell set, in no particular order */
+    pub fn cells(&self) -> impl Iterator<Item = (&Vec2i, &T)> {
+        self.cells.iter()
+    }
+
+    /** Every cell set, in order of y and then x. This sorts them on each call. */
+    pub fn iter(&self) -> impl Iterator<Item = (&Vec2i, &T)> {
+        let mut cells: Vec<(&Vec2i, &T)> = self.cells.iter().collect();
+        cells.sort_by_key(|(p, _)| (p.y, p.x));
+        cells.into_iter()
+    }
+
+    pub fn positions(&self) -> impl Iterator<Item = &Vec2i> {
+        self.iter().map(|(p, _)| p)
+    }
+
+    /**
+     * Draw the bounding box as text, using `f` to pick the character for each
+     * position and whatever is set there
+     */
+    pub fn render<F: Fn(&Vec2i, Option<&T>) -> char>(&self, y_axis: YAxis, f: F) -> String {
+        let mut text = String::new();
+        if let Some((min, max)) = &self.bounds {
+            let rows: Box<dyn Iterator<Item = i32>> = match y_axis {
+                YAxis::Down => Box::new(min.y..=max.y),
+                YAxis::Up => Box::new((min.y..=max.y).rev())
+            };
+            for y in rows {
+                for x in min.x..=max.x {
+                    let p = Vec2i::new(x, y);
+                    text.push(f(&p, self.get(&p)));
+                }
+                text.push('\n');
+            }
+        }
+        text
+    }
+}
+
+impl <T : Clone> SparseGrid<T> {
+    /**
+     * The bounding box as an `Array2D`, with `default` where nothing is set.
+     * The smallest corner of the box becomes (0, 0).
+     */
+    pub fn to_array2d(&self, default: &T) -> Array2D<T> {
+        match &self.bounds {
+            None => Array2D::from(vec![]),
+            Some((min, max)) => {
+                let mut grid = Array2D::with_shape((max.x - min.x + 1) as usize, (max.y - min.y + 1) as usize, default);
+                for (p, v) in &self.cells {
+                    grid[((p.x - min.x) as usize, (p.y - min.y) as usize)] = v.clone();
+                }
+                grid
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sparse_grid() {
+        let mut g = SparseGrid::new();
+        assert_eq!(g.bounds(), None);
+        assert_eq!(g.render(YAxis::Down, |_, _| '?'), "");
+
+        g.set(Vec2i::new(2, -1), 'a');
+        g.set(Vec2i::new(-3, 1), 'b');
+        assert_eq!(g.set(Vec2i::new(0, 0), 'c'), None);
+        *g.get_or_insert(Vec2i::new(0, 0), 'x') = 'd';
+        *g.get_or_insert(Vec2i::new(1, 1), 'e') = 'f';
+        assert_eq!(g.get(&Vec2i::new(0, 0)), Some(&'d'));
+        assert_eq!(g.get(&Vec2i::new(5, 5)), None);
+        assert_eq!(g.len(), 4);
+        assert_eq!(g.bounds(), Some((Vec2i::new(-3, -1), Vec2i::new(2, 1))));
+
+        let values: String = g.iter().map(|(_, c)| *c).collect();
+        assert_eq!(values, "adbf");
+        let mut unordered: Vec<char> = g.cells().map(|(_, c)| *c).collect();
+        unordered.sort();
+        assert_eq!(unordered, vec!['a', 'b', 'd', 'f']);
+
+        let text = |g: &SparseGrid<char>, y_axis| g.render(y_axis, |_, c| *c.unwrap_or(&'.'));
+        assert_eq!(text(&g, YAxis::Down), ".....a\n...d..\nb...f.\n");
+        assert_eq!(text(&g, YAxis::Up), "b...f.\n...d..\n.....a\n");
+        let marked = g.render(YAxis::Down, |p, c| if *p == Vec2i::new(0, 0) { '@' } else { *c.unwrap_or(&' ') });
+        assert_eq!(marked, "     a\n   @  \nb   f \n");
+
+        let dense = g.to_array2d(&'.');
+        assert_eq!((dense.len_x, dense.len_y), (6, 3));
+        assert_eq!(dense[(0, 2)], 'b');
+        assert_eq!(dense.to_string(), text(&g, YAxis::Down));
+
+        // Removing an edge cell shrinks the box
+        assert_eq!(g.remove(&Vec2i::new(-3, 1)), Some('b'));
+        assert_eq!(g.remove(&Vec2i::new(-3, 1)), None);
+        assert_eq!(g.bounds(), Some((Vec2i::new(0, -1), Vec2i::new(2, 1))));
+    }
+}
@@ -0,0 +1,204 @@
+use crate::vec2::Vec2;
+
+use num::{Integer, Signed};
+use std::ops::{Add, AddAssign, Index, IndexMut, Mul, Neg, Sub, SubAssign};
+
+/**
+ * An integer vector with any number of dimensions. Vectors are ordered by
+ * their first coordinate, then their second and so on.
+ */
+#[derive(PartialEq, Eq, Hash, Debug, Clone, Copy, PartialOrd, Ord)]
+pub struct VecN<T, const N: usize>(pub [T; N]);
+
+pub type Vec3<T> = VecN<T, 3>;
+pub type Vec3i = Vec3<i32>;
+
+impl <T : Integer + Copy, const N: usize> VecN<T, N> {
+    pub fn new(coords: [T; N]) -> Self {
+        Self(coords)
+    }
+
+    pub fn zero() -> Self {
+        Self([T::zero(); N])
+    }
+
+    /** Apply `f` to each coordinate */
+    pub fn map<F : Fn(T) -> T>(&self, f: F) -> Self {
+        let mut out = *self;
+        for c in out.0.iter_mut() {
+            *c = f(*c);
+        }
+        out
+    }
+
+    fn zip<F : Fn(T, T) -> T>(&self, other: &Self, f: F) -> Self {
+        let mut out = *self;
+        for (c, o) in out.0.iter_mut().zip(other.0.iter()) {
+            *c = f(*c, *o);
+        }
+        out
+    }
+
+    pub fn dot(&self, other: &Self) -> T {
+        self.0.iter().zip(other.0.iter()).fold(T::zero(), |s, (a, b)| s + *a * *b)
+    }
+
+    pub fn sq_length(&self) -> T {
+        self.dot(self)
+    }
+}
+
+impl <T : Integer + Copy + Signed, const N: usize> VecN<T, N> {
+    pub fn manhattan(&self) -> T {
+        self.0.iter().fold(T::zero(), |s, c| s + c.abs())
+    }
+
+    /** -1, 0 or 1 in each axis, following the sign of each coordinate */
+    pub fn signum(&self) -> Self {
+        self.map(|c| c.signum())
+    }
+}
+
+impl <T : Integer + Copy> Vec3<T> {
+    pub fn x(&self) -> T {
+        self.0[0]
+    }
+
+    pub fn y(&self) -> T {
+        self.0[1]
+    }
+
+    pub fn z(&self) -> T {
+        self.0[2]
+    }
+}
+
+impl <T, const N: usize> From<[T; N]> for VecN<T, N> {
+    fn from(coords: [T; N]) -> Self {
+        Self(coords)
+    }
+}
+
+impl <T : Integer> From<Vec2<T>> for VecN<T, 2> {
+    fn from(v: Vec2<T>) -> Self {
+        Self([v.x, v.y])
+    }
+}
+
+impl <T, const N: usize> Index<usize> for VecN<T, N> {
+    type Output = T;
+
+    fn index(&self, axis: usize) -> &T {
+        &self.0[axis]
+    }
+}
+
+impl <T, const N: usize> IndexMut<usize> for VecN<T, N> {
+    fn index_mut(&mut self, axis: usize) -> &mut T {
+        &mut self.0[axis]
+    }
+}
+
+impl <T : Integer + Copy, const N: usize> Add for VecN<T, N> {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        self.zip(&other, |a, b| a + b)
+    }
+}
+
+impl <T : Integer + Copy, const N: usize> AddAssign for VecN<T, N> {
+    fn add_assign(&mut self, other: Self) {
+        *self = *self + other;
+    }
+}
+
+impl <T : Integer + Copy, const N: usize> Sub for VecN<T, N> {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        self.zip(&other, |a, b| a - b)
+    }
+}
+
+impl <T : Integer + Copy, const N: usize> SubAssign for VecN<T, N> {
+    fn sub_assign(&mut self, other: Self) {
+        *self = *self - other;
+    }
+}
+
+/** Scaling by a scalar */
+impl <T : Integer + Copy, const N: usize> Mul<T> for VecN<T, N> {
+    type Output = Self;
+
+    fn mul(self, scalar: T) -> Self {
+        self.map(|c| c * scalar)
+    }
+}
+
+impl <T : Integer + Copy + Neg<Output = T>, const N: usize> Neg for VecN<T, N> {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        self.map(|c| -c)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn arithmetic() {
+        let a = Vec3i::new([1, -2, 3]);
+        let b = Vec3i::from([4, 0, -6]);
+        assert_eq!(a + b, Vec3i::new([5, -2, -3]));
+        assert_eq!(a - b, Vec3i::new([-3, -2, 9]));
+        assert_eq!(-a * 2, Vec3i::new([-2, 4, -6]));
+        assert_eq!(a.dot(&b), -14);
+        assert_eq!(a.manhattan(), 6);
+        assert_eq!(b.signum(), Vec3i::new([1, 0, -1]));
+        assert_eq!((a.x(), a.y(), a[2]), (1, -2, 3));
+
+        let mut c = Vec3i::zero();
+        c += a;
+        c -= b;
+        c[1] = 7;
+        assert_eq!(c, Vec3i::new([-3, 7, 9]));
+
+        assert!(Vec3i::new([0, 5, 5]) < Vec3i::new([1, 0, 0]));
+        let set: HashSet<Vec3i> = vec![a, b, a].into_iter().collect();
+        assert_eq!(set.len(), 2);
+
+        let v4 = VecN::new([1i64, 2, 3, 4]);
+        assert_eq!(v4.sq_length(), 30);
+        assert_eq!(VecN::from(Vec2::new(3, -4)).manhattan(), 7);
+    }
+
+    #[test]
+    fn n_body() {
+        // Day 12's example, which has total energy 179 after 10 steps
+        let mut pos = [
+            Vec3i::new([-1, 0, 2]),
+            Vec3i::new([2, -10, -7]),
+            Vec3i::new([4, -8, 8]),
+            Vec3i::new([3, 5, -1]),
+        ];
+        let mut vel = [Vec3i::zero(); 4];
+
+        for _ in 0..10 {
+            for i in 0..pos.len() {
+                for j in 0..pos.len() {
+                    vel[i] += (pos[j] - pos[i]).signum();
+                }
+            }
+            for (p, v) in pos.iter_mut().zip(&vel) {
+                *p += *v;
+            }
+        }
+
+        let energy: i32 = pos.iter().zip(&vel).map(|(p, v)| p.manhattan() * v.manhattan()).sum();
+        assert_eq!(energy, 179);
+    }
+}
@@ -1,5 +1,7 @@
 use num::Integer;
+use std::cmp::Ordering;
 use std::convert::{TryFrom, TryInto};
+use std::ops::{Add, AddAssign, Mul, Neg, Sub, SubAssign};
 
 #[derive(PartialEq, Eq, Hash, Debug, Clone, PartialOrd)]
 pub struct Vec2<T : Integer> {
@@ -17,29 +19,14 @@ impl <T> Vec2<T>
         Self { x: x, y: y }
     }
 
-    pub fn sqLength(&self) -> T {
+    pub fn sq_length(&self) -> T {
         self.x*self.x + self.y*self.y
     }
 
-    pub fn add(&self, other: &Self) -> Self {
-        Self::new(self.x + other.x, self.y + other.y)
-    }
-
-    pub fn sub(&self, other: &Self) -> Self {
-        Self::new(self.x - other.x, self.y - other.y)
-    }
-
     pub fn dot(&self, other: &Self) -> T {
         self.x * other.x + self.y * other.y
     }
 
-    pub fn mul(&self, scalar: T) -> Self {
-        Self {
-            x: self.x * scalar,
-            y: self.y * scalar
-        }
-    }
-
     pub fn elem_mul(&self, other: &Self) -> Self {
         Self {
             x: self.x * other.x,
@@ -56,6 +43,49 @@ impl <T> Vec2<T>
     }
 }
 
+/**
+ * Which way angles are measured. These are as drawn with y increasing upwards,
+ * so appear reversed where y increases down the screen.
+ */
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum Rotation {
+    Clockwise,
+    Anticlockwise
+}
+
+impl <T> Vec2<T>
+    where T : Integer + Copy + Into<i128>
+{
+    /**
+     * Compare two vectors by the angle turned from `from` to reach each of them,
+     * without using floats. Anticlockwise this gives the same order as `angle`.
+     * Vectors at the same angle are ordered shortest first, so only equal
+     * vectors compare equal, and the zero vector comes before everything.
+     */
+    pub fn cmp_angle(&self, other: &Self, from: &Self, rotation: Rotation) -> Ordering {
+        let wide = |v: &Self| -> (i128, i128) { (v.x.into(), v.y.into()) };
+        let (a, b, r) = (wide(self), wide(other), wide(from));
+        assert!(r != (0, 0), "Can't measure angles from the zero vector");
+
+        let sign = if rotation == Rotation::Anticlockwise { 1 } else { -1 };
+        let cross = |u: (i128, i128), v: (i128, i128)| sign * (u.0 * v.1 - u.1 * v.0);
+        let dot = |u: (i128, i128), v: (i128, i128)| u.0 * v.0 + u.1 * v.1;
+
+        // Whether the angle from the reference is at least half a turn
+        let half = |v| {
+            let c = cross(r, v);
+            c < 0 || (c == 0 && dot(r, v) < 0)
+        };
+
+        // Within a half turn, the second vector is further round if turning
+        // from the first to it goes the right way
+        half(a).cmp(&half(b))
+            .then_with(|| 0.cmp(&cross(a, b)))
+            .then_with(|| dot(a, a).cmp(&dot(b, b)))
+            .then_with(|| a.cmp(&b))
+    }
+}
+
 impl Vec2<i32> {
     pub fn manhatten(&self) -> i32 {
         self.x.abs() + self.y.abs()
@@ -87,6 +117,150 @@ impl Vec2<i32> {
     }
 }
 
+impl <T : Integer + Copy> Add for Vec2<T> {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self::new(self.x + other.x, self.y + other.y)
+    }
+}
+
+impl <T : Integer + Copy> Add for &Vec2<T> {
+    type Output = Vec2<T>;
+
+    fn add(self, other: Self) -> Vec2<T> {
+        Vec2::new(self.x + other.x, self.y + other.y)
+    }
+}
+
+impl <T : Integer + Copy> AddAssign for Vec2<T> {
+    fn add_assign(&mut self, other: Self) {
+        self.x = self.x + other.x;
+        self.y = self.y + other.y;
+    }
+}
+
+impl <T : Integer + Copy> Sub for Vec2<T> {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        Self::new(self.x - other.x, self.y - other.y)
+    }
+}
+
+impl <T : Integer + Copy> Sub for &Vec2<T> {
+    type Output = Vec2<T>;
+
+    fn sub(self, other: Self) -> Vec2<T> {
+        Vec2::new(self.x - other.x, self.y - other.y)
+    }
+}
+
+impl <T : Integer + Copy> SubAssign for Vec2<T> {
+    fn sub_assign(&mut self, other: Self) {
+        self.x = self.x - other.x;
+        self.y = self.y - other.y;
+    }
+}
+
+/** Scaling by a scalar */
+impl <T : Integer + Copy> Mul<T> for Vec2<T> {
+    type Output = Self;
+
+    fn mul(self, scalar: T) -> Self {
+        Self::new(self.x * scalar, self.y * scalar)
+    }
+}
+
+impl <T : Integer + Copy + Neg<Output = T>> Neg for Vec2<T> {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self::new(-self.x, -self.y)
+    }
+}
+
+/**
+ * A compass direction on a grid. North is towards smaller y, as rows are
+ * numbered down the screen.
+ */
+#[derive(PartialEq, Eq, Hash, Debug, Clone, Copy)]
+pub enum Heading {
+    North,
+    East,
+    South,
+    West
+}
+
+/** The turn taking one heading to another */
+#[derive(PartialEq, Eq, Hash, Debug, Clone, Copy)]
+pub enum Turn {
+    Ahead,
+    Left,
+    Right,
+    Back
+}
+
+impl Heading {
+    /** Clockwise from north */
+    pub const ALL: [Heading; 4] = [Heading::North, Heading::East, Heading::South, Heading::West];
+
+    fn index(self) -> usize {
+        self as usize
+    }
+
+    fn from_index(i: usize) -> Self {
+        Self::ALL[i % 4]
+    }
+
+    pub fn turn_right(self) -> Self {
+        Self::from_index(self.index() + 1)
+    }
+
+    pub fn turn_left(self) -> Self {
+        Self::from_index(self.index() + 3)
+    }
+
+    pub fn reverse(self) -> Self {
+        Self::from_index(self.index() + 2)
+    }
+
+    pub fn turn(self, turn: Turn) -> Self {
+        match turn {
+            Turn::Ahead => self,
+            Turn::Left => self.turn_left(),
+            Turn::Right => self.turn_right(),
+            Turn::Back => self.reverse()
+        }
+    }
+
+    /** The turn to make to face `to` after facing this way */
+    pub fn turn_to(self, to: Heading) -> Turn {
+        match (to.index() + 4 - self.index()) % 4 {
+            0 => Turn::Ahead,
+            1 => Turn::Right,
+            2 => Turn::Back,
+            _ => Turn::Left
+        }
+    }
+
+    /** The heading of a unit vector, or `None` for any other vector */
+    pub fn from_vec(v: &Vec2i) -> Option<Self> {
+        Self::ALL.iter().cloned().find(|h| Vec2i::from(*h) == *v)
+    }
+}
+
+impl From<Heading> for Vec2i {
+    fn from(heading: Heading) -> Self {
+        match heading {
+            Heading::North => Vec2i::new(0, -1),
+            Heading::East => Vec2i::new(1, 0),
+            Heading::South => Vec2i::new(0, 1),
+            Heading::West => Vec2i::new(-1, 0)
+        }
+    }
+}
+
 impl TryFrom<Vec2i> for Vec2u {
     type Error = <usize as TryFrom<i32>>::Error;
 
@@ -113,6 +287,97 @@ mod test {
         assert_eq!(Vec2i::new(0, 5).gcd_normalized(), Vec2i::new(0, 1));
     }
 
+    #[test]
+    fn operators() {
+        let a = Vec2i::new(1, 2);
+        let b = Vec2i::new(-3, 5);
+        assert_eq!(a.clone() + b.clone(), Vec2i::new(-2, 7));
+        assert_eq!(&a - &b, Vec2i::new(4, -3));
+        assert_eq!(b.clone() * 3, Vec2i::new(-9, 15));
+        assert_eq!(-a.clone(), Vec2i::new(-1, -2));
+
+        let mut c = a.clone();
+        c += b.clone();
+        c -= Vec2i::new(1, 1);
+        assert_eq!(c, Vec2i::new(-3, 6));
+        assert_eq!(c.sq_length(), 45);
+        assert_eq!(Vec2u::new(3, 4) + Vec2u::new(1, 1), Vec2u::new(4, 5));
+    }
+
+    #[test]
+    fn heading() {
+        use Heading::*;
+
+        assert_eq!(North.turn_right(), East);
+        assert_eq!(North.turn_left(), West);
+        assert_eq!(West.turn_right(), North);
+        assert_eq!(East.reverse(), West);
+
+        for h in Heading::ALL.iter().cloned() {
+            let v = Vec2i::from(h);
+            assert_eq!(v.manhatten(), 1);
+            assert_eq!(Heading::from_vec(&v), Some(h));
+            assert_eq!(Vec2i::from(h.reverse()), -v);
+            for turn in [Turn::Ahead, Turn::Left, Turn::Right, Turn::Back].iter().cloned() {
+                assert_eq!(h.turn_to(h.turn(turn)), turn);
+            }
+        }
+
+        assert_eq!(Vec2i::from(North), Vec2i::new(0, -1));
+        assert_eq!(South.turn_to(East), Turn::Left);
+        assert_eq!(Heading::from_vec(&Vec2i::new(1, 1)), None);
+        assert_eq!(Heading::from_vec(&Vec2i::new(0, 0)), None);
+    }
+
+    #[test]
+    fn cmp_angle() {
+        // Every vector in a square, against the float version
+        let square: Vec<Vec2i> = (-6..=6).flat_map(|x| (-6..=6).map(move |y| Vec2i::new(x, y))).collect();
+        let references = [(0, 1), (1, 0), (0, -1), (-1, 0), (1, 1), (-1, -1), (2, -5), (-3, 4)];
+
+        for (rx, ry) in references.iter() {
+            let from = Vec2i::new(*rx, *ry);
+            for rotation in [Rotation::Anticlockwise, Rotation::Clockwise].iter().cloned() {
+                let angle = |v: &Vec2i| {
+                    let a = v.angle(&from);
+                    if rotation == Rotation::Clockwise && a != 0.0 {
+                        std::f64::consts::PI * 2.0 - a
+                    } else {
+                        a
+                    }
+                };
+
+                for a in &square {
+                    for b in &square {
+                        let (fa, fb) = (angle(a), angle(b));
+                        let expected = if *a == Vec2i::new(0, 0) || *b == Vec2i::new(0, 0) || (fa - fb).abs() < 1e-9 {
+                            // Same angle, so shortest first
+                            a.sq_length().cmp(&b.sq_length()).then_with(|| (a.x, a.y).cmp(&(b.x, b.y)))
+                        } else {
+                            fa.partial_cmp(&fb).unwrap()
+                        };
+                        assert_eq!(
+                            a.cmp_angle(b, &from, rotation), expected,
+                            "{:?} vs {:?} {:?} from {:?}", a, b, rotation, from
+                        );
+                    }
+                }
+            }
+        }
+
+        // Clockwise from up, as day 10's laser sweeps
+        let up = Vec2i::new(0, 1);
+        let mut l = vec![Vec2i::new(2, -1), Vec2i::new(-2, -5), Vec2i::new(1, 1), Vec2i::new(2, 1), Vec2i::new(-3, 2), Vec2i::new(1, -1)];
+        l.sort_by(|a, b| a.cmp_angle(b, &up, Rotation::Clockwise));
+        assert_eq!(l, vec![Vec2i::new(1, 1), Vec2i::new(2, 1), Vec2i::new(2, -1), Vec2i::new(1, -1), Vec2i::new(-2, -5), Vec2i::new(-3, 2)]);
+
+        // Products are taken wide enough not to overflow
+        let big = Vec2::<i64>::new(i64::MAX, i64::MIN + 1);
+        let from = Vec2::<i64>::new(1, 0);
+        assert_eq!(big.cmp_angle(&Vec2::new(1, -1), &from, Rotation::Anticlockwise), Ordering::Greater);
+        assert_eq!(Vec2::<u32>::new(1, 3).cmp_angle(&Vec2::new(3, 1), &Vec2::new(1, 0), Rotation::Anticlockwise), Ordering::Greater);
+    }
+
     #[test]
     fn angle() {
         let up = Vec2i::new(0, -1);
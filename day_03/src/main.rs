@@ -7,7 +7,7 @@ use std::collections::{HashMap, HashSet};
 use common::vec2::Vec2i;
 
 fn travel(visited: &mut HashMap<Vec2i, i32>, start: &Vec2i, motion: &Vec2i) -> Vec2i {
-    let end = start.add(motion);
+    let end = start + motion;
     // Normalize to quadrant direction
     let n_dir = Vec2i {
         x: motion.x / motion.manhatten(),
@@ -20,7 +20,7 @@ fn travel(visited: &mut HashMap<Vec2i, i32>, start: &Vec2i, motion: &Vec2i) -> V
 
     while pos != end {
         // Progress
-        pos = pos.add(&n_dir);
+        pos = &pos + &n_dir;
         cur_step += 1;
         visited.entry(pos.clone()).or_insert(cur_step);
     }
@@ -1,13 +1,10 @@
 use std::collections::{HashMap, VecDeque};
 
-mod direction;
-use direction::Direction;
-
-use common::vec2::Vec2i;
+use common::vec2::{Rotation, Vec2i};
 
 fn count_visible(asteroids: Vec<Vec2i>, from: Vec2i) -> HashMap<Vec2i, VecDeque<Vec2i>> {
     // Project from new perspective
-    let mut asteroids : Vec<Vec2i> = asteroids.iter().map(|v| v.sub(&from)).collect();
+    let mut asteroids : Vec<Vec2i> = asteroids.iter().map(|v| v - &from).collect();
 
     // Sort by manhatten lengths
     asteroids.sort_by(|a, b| a.manhatten().cmp(&b.manhatten()));
@@ -29,19 +26,9 @@ fn get_200th(mut map: HashMap<Vec2i, VecDeque<Vec2i>>) -> Option<Vec2i> {
     // Since it's sorted by clockwise angles from up, the first is always the first asteroid to zap
     let up = Vec2i::new(0, -1);
     let mut keylist : Vec<Vec2i> = map.keys().cloned().collect();
-    
-    // Option 1: sort by angle
-    //keylist.sort_by(|a, b| a.angle(&up).partial_cmp(&b.angle(&up)).unwrap());
-    
-    // Option 2: Sort by direction (no floats!)
-    let mut keylist = keylist.drain(..)
-                        .map(|v| Direction::from(Vec2i::new(v.x, -v.y)))
-                        .collect::<Vec<Direction>>();
-    keylist.sort();
-    let keylist = keylist.drain(..)
-                    .map(|d| d.to_vec())
-                    .map(|v| Vec2i::new(v.x, -v.y))
-                    .collect::<Vec<Vec2i>>();
+
+    // Sort by angle (no floats!) - anticlockwise with y up is clockwise on the screen
+    keylist.sort_by(|a, b| a.cmp_angle(b, &up, Rotation::Anticlockwise));
 
     let mut count = 0;
     loop {
@@ -86,6 +73,6 @@ fn main() {
     println!("Max = {} (at {}, {})", max, best_coord.x, best_coord.y);
 
     let v200 = get_200th(best_map).unwrap();
-    let v200 = best_coord.add(&v200);
+    let v200 = &best_coord + &v200;
     println!("200th = {}, {}", v200.x, v200.y);
 }
@@ -1,9 +1,8 @@
-use std::collections::HashMap;
-
 mod intcode;
 use intcode::IntCodeRunner;
 
-use common::vec2::Vec2i;
+use common::sparse_grid::{SparseGrid, YAxis};
+use common::vec2::{Heading, Vec2i};
 
 fn main() {
     let contents = std::fs::read_to_string("input.txt").expect("Couldn't read file");
@@ -16,18 +15,17 @@ fn main() {
     let mut robot = IntCodeRunner::new(ops);
 
     // Map of each visited node and colour
-    let mut visited = HashMap::<Vec2i, i64>::new();
+    let mut visited = SparseGrid::<i64>::new();
     
-    let directions = vec![Vec2i::new(0, 1), Vec2i::new(1, 0), Vec2i::new(0, -1), Vec2i::new(-1, 0)];
-    let mut cur_dir = 0;
+    let mut heading = Heading::North;
 
     let mut pos = Vec2i::new(0, 0);
 
     // Start panel is white
-    visited.insert(pos.clone(), 1);
+    visited.set(pos.clone(), 1);
     
     loop {
-        let tile = visited.entry(pos.clone()).or_insert(0);
+        let tile = visited.get_or_insert(pos.clone(), 0);
 
         robot.push_input(*tile);
 
@@ -35,39 +33,18 @@ fn main() {
             *tile = col;
             
             if robot.nextio().unwrap() == 1 {
-                cur_dir = (cur_dir + 1) % 4;
+                heading = heading.turn_right();
             } else {
-                cur_dir = if cur_dir == 0 { 3 } else { cur_dir - 1 };
+                heading = heading.turn_left();
             }
         } else {
             break;
         }
 
-        pos = pos.add(&directions[cur_dir]);
+        pos += heading.into();
     }
 
     println!("Visited: {}", visited.len());
 
-    let max_x = visited.iter().map(|(k, _)| k.x).max().unwrap();
-    let min_x = visited.iter().map(|(k, _)| k.x).min().unwrap();
-    let max_y = visited.iter().map(|(k, _)| k.y).max().unwrap();
-    let min_y = visited.iter().map(|(k, _)| k.y).min().unwrap();
-
-
-    for y in (min_y..=max_y).rev() {
-        for x in min_x..=max_x {
-            let p = Vec2i::new(x, y);
-
-            let mut o = ' ';
-            if let Some(c) = visited.get(&p) {
-                if *c == 1 {
-                    o = '#';
-                }
-            }
-
-            print!("{}", o);
-        }
-
-        println!("");
-    }
+    print!("{}", visited.render(YAxis::Down, |_, c| if c == Some(&1) { '#' } else { ' ' }));
 }
@@ -1,5 +1,6 @@
 use regex::Regex;
-use num::Integer;
+use common::cycle;
+use common::math::lcm;
 
 #[derive(Clone, Hash, Eq, PartialEq, Debug)]
 struct Axis {
@@ -58,23 +59,20 @@ fn main() {
     let mut bodies : Vec<Vec<Axis>> = transpose(bodies).drain(..)
         .map(|mut v| v.drain(..).map(|i| Axis { p: i, v: 0 }).collect()).collect();
 
-    // We know the loop starts at zero
-    // and we know each Axis is unrelated to each other
-    // So we're actually just looking for the first collision in each axis
-    // and finding the LCM of all
-    let l = bodies.drain(..).map(|mut axis| {
-        let first = axis.clone();
+    // Each axis is unrelated to the others, so the whole system repeats after
+    // the LCM of each axis' loop. Every step can be undone, so each loop
+    // starts from the initial state.
+    let l = lcm(bodies.drain(..).map(|axis| {
+        let cycle = cycle::brent(axis, |a| {
+            let mut a = a.clone();
+            update(&mut a);
+            a
+        });
+        assert_eq!(cycle.start, 0);
 
-        let mut count = 1usize;
-        update(&mut axis);
-        while first != axis {
-            update(&mut axis);
-            count += 1;
-        }
-
-        println!("count={}", count);
-        return count;
-    }).fold(1, |l, x| l.lcm(&x));
+        println!("count={}", cycle.length);
+        cycle.length
+    }));
 
     println!("l = {}", l);
 }
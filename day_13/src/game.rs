@@ -1,7 +1,13 @@
-use std::sync::mpsc::{Receiver, Sender};
-use std::collections::HashMap;
 use std::time::{Duration, Instant};
 
+use common::intcode::stream::AsyncRunner;
+use common::sparse_grid::SparseGrid;
+use common::vec2::Vec2i;
+
+use futures::channel::mpsc::{UnboundedReceiver, UnboundedSender};
+use futures::future::FutureExt;
+use futures::stream::StreamExt;
+
 use quicksilver::{
     Result,
     geom::{Circle, Line, Rectangle, Transform, Triangle, Vector},
@@ -16,9 +22,11 @@ const PADDLE: i32 = 3;
 const BALL: i32 = 4;
 
 pub struct DrawGeometry {
-    itx: Sender<i32>,
-    orx: Receiver<(i32, i32, i32)>,
-    screen: HashMap<(i32, i32), i32>,
+    itx: UnboundedSender<i64>,
+    machine: AsyncRunner<i64, UnboundedReceiver<i64>>,
+    /** Outputs of a tile not yet complete */
+    pending: Vec<i64>,
+    screen: SparseGrid<i32>,
     score: i32,
     last_in_time: Instant,
     last_input: i32,
@@ -27,11 +35,12 @@ pub struct DrawGeometry {
 }
 
 impl DrawGeometry {
-    fn with_rx(itx: Sender<i32>, orx: Receiver<(i32, i32, i32)>) -> Result<Self> {
+    fn with_machine(itx: UnboundedSender<i64>, machine: AsyncRunner<i64, UnboundedReceiver<i64>>) -> Result<Self> {
         Ok(Self {
             itx: itx,
-            orx: orx,
-            screen: HashMap::new(),
+            machine: machine,
+            pending: Vec::new(),
+            screen: SparseGrid::new(),
             score: 0,
             last_in_time: Instant::now(),
             last_input: 100,
@@ -46,14 +55,37 @@ impl DrawGeometry {
             self.last_in_time = Instant::now();
             self.last_input = j;
 
-            self.itx.send(j).unwrap();
+            self.itx.unbounded_send(j as i64).unwrap();
+        }
+    }
+
+    /**
+     * Run the machine until it waits for the joystick or has used its step
+     * budget for this frame, returning the (x, y, tile) triples it drew.
+     * None once it has halted.
+     */
+    fn run_machine(&mut self) -> Option<Vec<(i32, i32, i32)>> {
+        let mut tiles = Vec::new();
+        loop {
+            match self.machine.next().now_or_never() {
+                Some(Some(Ok(v))) => {
+                    self.pending.push(v);
+                    if let [x, y, b] = self.pending[..] {
+                        tiles.push((x as i32, y as i32, b as i32));
+                        self.pending.clear();
+                    }
+                }
+                Some(Some(Err(e))) => panic!("{}", e),
+                Some(None) => return None,
+                None => return Some(tiles),
+            }
         }
     }
 
     fn find(&mut self, block: i32) -> Vec<(i32, i32)> {
-        self.screen.iter()
+        self.screen.cells()
             .filter(|(_, b)| **b == block)
-            .map(|(v, _)| *v)
+            .map(|(v, _)| (v.x, v.y))
             .collect()
     }
 }
@@ -65,14 +97,16 @@ impl State for DrawGeometry {
 
     fn draw(&mut self, window: &mut Window) -> Result<()> {
         // Receive updated positions
-        while let Ok((x, y, b)) = self.orx.try_recv() {
+        let tiles = self.run_machine().unwrap_or_else(|| {
+            window.close();
+            Vec::new()
+        });
+        for (x, y, b) in tiles {
             if x < 0 {
                 self.score = b;
                 println!("Score = {}", self.score);
-            } else if y < 0 {
-                window.close();
             } else {
-                self.screen.insert((x, y), b);
+                self.screen.set(Vec2i::new(x, y), b);
 
                 if b == BALL || b == PADDLE {
                     self.update_counter += 1;
@@ -97,11 +131,11 @@ impl State for DrawGeometry {
             let paddle = self.find(PADDLE)[0];
             
             if ball.0 > paddle.0 {
-                self.itx.send(1).unwrap();
+                self.itx.unbounded_send(1).unwrap();
             } else if ball.0 < paddle.0 {
-                self.itx.send(-1).unwrap();
+                self.itx.unbounded_send(-1).unwrap();
             } else {
-                self.itx.send(0).unwrap();
+                self.itx.unbounded_send(0).unwrap();
             }
 
             self.update_counter = 0;
@@ -113,7 +147,7 @@ impl State for DrawGeometry {
         window.clear(Color::BLACK)?;
         
         // Draw screen
-        for ((x, y), b) in self.screen.iter() {
+        for (p, b) in self.screen.cells() {
             let col = match *b {
                 WALL => Color::WHITE,
                 BLOCK => Color::GREEN,
@@ -122,14 +156,14 @@ impl State for DrawGeometry {
                 _ => Color::BLACK
             };
 
-            window.draw(&Rectangle::new((x * unit, y * unit), (unit, unit)), Col(col));
+            window.draw(&Rectangle::new((p.x * unit, p.y * unit), (unit, unit)), Col(col));
         }
         Ok(())
     }
 }
 
-pub fn start(itx: Sender<i32>, orx: Receiver<(i32, i32, i32)>) {
+pub fn start(itx: UnboundedSender<i64>, machine: AsyncRunner<i64, UnboundedReceiver<i64>>) {
     run_with("Draw", Vector::new(800, 800), Settings::default(), move || {
-        DrawGeometry::with_rx(itx, orx)
+        DrawGeometry::with_machine(itx, machine)
     });
 }
\ No newline at end of file
@@ -1,4 +1,5 @@
 use regex::Regex;
+use common::math::binary_search;
 use std::collections::{HashMap, HashSet};
 
 use itertools::{
@@ -94,27 +95,13 @@ impl Reactions {
 }
 
 fn bin_search<F : Fn(i64) -> i64>(target: i64, min: i64, max: i64, f: F) -> i64 {
-    let mut min = min;
-    let mut max = max;
-
-    // This is an exercise in goal post moving until we converge on a value
     // We must not be bigger than target, but we must be as close to it as possible
-
-    while (max - min) > 1 {
-        let mid = (max - min) / 2 + min;
-        
-        let x = f(mid);
-
-        if x > target {
-            max = mid;
-        } else if x < target {
-            min = mid;
-        } else {
-            return mid;
-        }
+    // so find the first value which is too big and step back one, though never
+    // below min
+    match binary_search(min, max, |x| f(x) > target) {
+        Some(too_big) => (too_big - 1).max(min),
+        None => max - 1
     }
-
-    return min;
 }
 
 fn main() {
@@ -1,12 +1,12 @@
-mod intcode;
+use common::intcode::stream::AsyncRunner;
+use common::intcode::IntCodeMachine;
+use common::search::bfs;
+use common::sparse_grid::{SparseGrid, YAxis};
+use common::vec2::{Heading, Vec2i};
 
-use intcode::IntCodeRunner;
-use common::vec2::Vec2i;
-
-use std::sync::mpsc::channel;
-use std::thread;
-
-use std::collections::{HashMap, HashSet};
+use futures::channel::mpsc::unbounded;
+use futures::executor::block_on;
+use futures::stream::StreamExt;
 
 #[derive(PartialEq, Clone, Copy, Debug)]
 enum Block {
@@ -16,29 +16,21 @@ enum Block {
     Oxygen,
 }
 
-fn get_direction(id: i32) -> Option<Vec2i> {
-    match id {
-        1 => Some(Vec2i::new(0, 1)),
-        2 => Some(Vec2i::new(0, -1)),
-        3 => Some(Vec2i::new(-1, 0)),
-        4 => Some(Vec2i::new(1, 0)),
-        _ => None,
-    }
-}
+/** The droid's movement commands, in the order it tries them */
+const COMMANDS: [(i32, Heading); 4] = [
+    (1, Heading::North),
+    (2, Heading::South),
+    (3, Heading::West),
+    (4, Heading::East),
+];
 
-fn get_direction_id(v: &Vec2i) -> Option<i32> {
-    match v {
-        Vec2i { x: 0, y: 1 } => Some(1),
-        Vec2i { x: 0, y: -1 } => Some(2),
-        Vec2i { x: -1, y: 0 } => Some(3),
-        Vec2i { x: 1, y: 0 } => Some(4),
-        _ => None,
-    }
+fn command(heading: Heading) -> i32 {
+    COMMANDS.iter().find(|(_, h)| *h == heading).unwrap().0
 }
 
 struct Robot {
     visit_stack: Vec<Vec2i>, // Currently visited all these tiles from the start
-    map: HashMap<Vec2i, (Block, i32)>, // Map of area, each location mapping to (contents, distance from start)
+    map: SparseGrid<(Block, i32)>, // Map of area, each location mapping to (contents, distance from start)
     curpos: Vec2i,
     backtracking: bool
 }
@@ -47,11 +39,11 @@ impl Robot {
     fn new() -> Self {
         let mut s = Self {
             visit_stack: Vec::new(),
-            map: HashMap::new(),
+            map: SparseGrid::new(),
             curpos: Vec2i::new(0, 0),
             backtracking: false
         };
-        s.map.insert(s.curpos.clone(), (Block::Air, 0));
+        s.map.set(s.curpos.clone(), (Block::Air, 0));
         return s;
     }
 
@@ -76,19 +68,18 @@ impl Robot {
 
     fn next_move(&mut self) -> Option<i32> {
         // find a direction to explore
-        for dir_id in 1..=4 {
-            let dir = get_direction(dir_id).unwrap();
-            let p = self.curpos.add(&dir);
+        for (cmd, heading) in COMMANDS.iter() {
+            let p = &self.curpos + &Vec2i::from(*heading);
             if self.should_visit(&p) {
                 self.visit_stack.push(self.curpos.clone());
                 self.curpos = p;
-                return Some(dir_id);
+                return Some(*cmd);
             }
         }
 
         // No place to go, backtrack one instead
         if let Some(last_pos) = self.visit_stack.pop() {
-            let d = get_direction_id(&last_pos.sub(&self.curpos));
+            let d = Heading::from_vec(&(&last_pos - &self.curpos)).map(command);
             self.curpos = last_pos;
             self.backtracking = true;
             return d;
@@ -106,7 +97,7 @@ impl Robot {
                 }
 
                 // Hit a wall, record
-                self.map.insert(
+                self.map.set(
                     self.curpos.clone(),
                     (Block::Wall, self.visit_stack.len() as i32),
                 );
@@ -115,14 +106,14 @@ impl Robot {
             }
             1 => {
                 // Found air
-                self.map.insert(
+                self.map.set(
                     self.curpos.clone(),
                     (Block::Air, self.visit_stack.len() as i32),
                 );
             }
             2 => {
                 // Found oxygen
-                self.map.insert(
+                self.map.set(
                     self.curpos.clone(),
                     (Block::Oxygen, self.visit_stack.len() as i32),
                 );
@@ -163,96 +154,56 @@ impl Robot {
             )
             .nth(0).unwrap();
 
-        // Iterate until we have no more to visit
-        let mut iter_count = 0;
-        let mut to_visit = HashSet::new();
-        to_visit.insert(o2_start);
-
-        while to_visit.len() > 0 {
-            iter_count += 1;
-
-            let mut adjacent = HashSet::new();
-
-            for pos in to_visit {
-                // Set this position to oxygen
-                self.map.insert(pos.clone(), (Block::Oxygen, 0));
-
-                // Find adjacent values to these that are air
-                for dir_id in 1..=4 {
-                    let dir = get_direction(dir_id).unwrap();
-                    let p = pos.add(&dir);
-
-                    if self.get(&p) == Block::Air {
-                        adjacent.insert(p);
-                    }
-                }
-            }
-
-            // Next iterations visitations are just the ones adjacent to this iterations
-            to_visit = adjacent;
+        // Oxygen spreads one step a minute, so the time to fill is the distance
+        // to the furthest air
+        let search = bfs(o2_start, |pos| {
+            Heading::ALL.iter()
+                .map(|h| pos + &Vec2i::from(*h))
+                .filter(|p| self.get(p) == Block::Air)
+                .collect::<Vec<_>>()
+        });
+
+        for pos in search.distances.keys() {
+            self.map.set(pos.clone(), (Block::Oxygen, 0));
         }
 
-        // the last iteration we're double counting;
-        // Imagine we only had the two squares
-        //  Iter 0: #O.#
-        //  Iter 1: #OO#
-        // After iter 1 the count is 2, but it only took one iteration
-        return iter_count - 1;
+        return *search.distances.values().max().unwrap() as i32;
     }
 
     fn print_map(&self) {
-        let minx = self.map.keys().map(|v| v.x).min().unwrap();
-        let maxx = self.map.keys().map(|v| v.x).max().unwrap();
-        let miny = self.map.keys().map(|v| v.y).min().unwrap();
-        let maxy = self.map.keys().map(|v| v.y).max().unwrap();
-    
-        for y in miny..=maxy {
-            for x in minx..=maxx {
-                let c = if x == 0 && y == 0 {
-                    "@"
-                } else {
-                    match self.get(&Vec2i::new(x, y)) {
-                        Block::Unknown => " ",
-                        Block::Air => ".",
-                        Block::Oxygen => "O",
-                        Block::Wall => "#",
-                    }
-                };
-                print!("{}", c);
+        let text = self.map.render(YAxis::Up, |p, cell| {
+            if p.x == 0 && p.y == 0 {
+                '@'
+            } else {
+                match cell.map_or(Block::Unknown, |(b, _)| *b) {
+                    Block::Unknown => ' ',
+                    Block::Air => '.',
+                    Block::Oxygen => 'O',
+                    Block::Wall => '#',
+                }
             }
-            println!("");
-        }
+        });
+        print!("{}", text);
     }
 }
 
 fn main() {
-    let (otx, orx) = channel::<i32>();
-
-    let (itx, irx) = channel::<i32>();
-
-    // Start background intcode machine
-    thread::spawn(move || {
-        let contents = String::from_utf8_lossy(include_bytes!("../input.txt")).to_string();
-        //let contents = std::fs::read_to_string("input.txt").expect("Couldn't read file");
-        let mut machine = IntCodeRunner::load_file(contents, irx);
+    let contents = String::from_utf8_lossy(include_bytes!("../input.txt")).to_string();
+    //let contents = std::fs::read_to_string("input.txt").expect("Couldn't read file");
 
-        while let Some(x) = machine.next() {
-            otx.send(x).unwrap();
-        }
-
-        // Send exit signal
-        otx.send(-1).unwrap();
-    });
+    let (itx, irx) = unbounded::<i64>();
+    let mut outputs = AsyncRunner::new(IntCodeMachine::load_file(contents), irx);
 
     let mut bot = Robot::new();
 
-    while let Some(m) = bot.next_move() {
-        itx.send(m).unwrap();
-        let o = orx.recv().unwrap();
-        assert!(o >= 0);
+    block_on(async {
+        while let Some(m) = bot.next_move() {
+            itx.unbounded_send(m as i64).unwrap();
+            let o = outputs.next().await.expect("Robot stopped").unwrap();
 
-        bot.process_input(o);
-    }
+            bot.process_input(o as i32);
+        }
+    });
 
     //println!("{:?}", bot.map);
 
@@ -1,7 +1,6 @@
-mod intcode;
-
-use intcode::IntCodeRunner;
-use common::vec2::Vec2i;
+use common::intcode::{IntCodeIO, IntCodeMachine, IntCodeRunner};
+use common::array2d::{Array2D, FromGridChar, Neighbourhood, ToGridChar};
+use common::vec2::{Heading, Turn, Vec2i, Vec2u};
 
 use itertools::Itertools;
 
@@ -13,91 +12,69 @@ enum Block {
     Air,
     Scaffold,
     Visited,
-    Robot(Vec2i)
+    Robot(Heading)
+}
+
+impl FromGridChar for Block {
+    fn from_grid_char(c: char) -> Option<Self> {
+        match c {
+            '#' => Some(Block::Scaffold),
+            '.' => Some(Block::Air),
+            '<' => Some(Block::Robot(Heading::West)),
+            '>' => Some(Block::Robot(Heading::East)),
+            '^' => Some(Block::Robot(Heading::North)),
+            'v' => Some(Block::Robot(Heading::South)),
+            _ => None
+        }
+    }
+}
+
+impl ToGridChar for Block {
+    fn to_grid_char(&self) -> char {
+        match self {
+            Block::Air => '.',
+            Block::Scaffold => '#',
+            Block::Robot(_) => '@',
+            Block::Visited => 'V'
+        }
+    }
 }
 
-fn printmap(map: &Vec<Vec<Block>>) {
+fn printmap(map: &Array2D<Block>) {
     print!("    ");
-    for x in 0..map[0].len() {
+    for x in 0..map.len_x {
         print!("{}", x % 10);
     }
     println!("");
-    for (y, row) in map.iter().enumerate() {
+    for (y, row) in map.rows().enumerate() {
         print!("{:2}  ", y);
         for c in row {
-            let c = match c {
-                Block::Air => '.',
-                Block::Scaffold => '#',
-                Block::Robot(_) => '@',
-                Block::Visited => 'V',
-                _ => '?'
-            };
-            print!("{}", c);
+            print!("{}", c.to_grid_char());
         } 
         println!("");
     }
 }
 
 fn find_path(mut machine: IntCodeRunner) -> Vec<String> {
-    let mut map: Vec<Vec<Block>> = Vec::new();
-
-    map.push(Vec::new());
-    let mut y = 0;
-    while let Some(i) = machine.next() {
-        let b = match i {
-            35 => Block::Scaffold,
-            46 => Block::Air,
-            10 => {
-                map.push(Vec::new());
-                y += 1;
-                continue;
-            }
-            60 => Block::Robot(Vec2i::new(-1, 0)),
-            62 => Block::Robot(Vec2i::new(1, 0)),
-            94 => Block::Robot(Vec2i::new(0, -1)),
-            86 => Block::Robot(Vec2i::new(0, 1)),
-            _ => panic!("Unknown character {}", i)
-        };
-
-        map[y].push(b);
+    let mut camera = String::new();
+    while let IntCodeIO::Output(i) = machine.next() {
+        camera.push(i as u8 as char);
     }
 
-    printmap(&map);
-
-    let directions = vec![
-        Vec2i::new(0, 1),
-        Vec2i::new(1, 0),
-        Vec2i::new(0, -1),
-        Vec2i::new(-1, 0)
-    ];
-
-    let len_x = map[0].len();
-
-    // Hack fix - remove empty rows
-    let mut map: Vec<Vec<Block>> = map.drain(..).filter(|v| v.len() == len_x).collect();
+    let map: Array2D<Block> = Array2D::parse(&camera).unwrap_or_else(|e| panic!("{}", e));
 
-    let len_y = map.len();
+    printmap(&map);
 
     let mut alignment = 0;
 
-    for y in 0..len_y {
-        for x in 0..len_x {
-            if map[y][x] == Block::Scaffold {
-                let p = Vec2i::new(x as i32, y as i32);
-                let mut neighbors = 0;
-                for dir in &directions {
-                    let n = p.add(dir);
-                    if n.x >= 0 && (n.x as usize) < len_x && n.y >= 0 && (n.y as usize) < len_y 
-                        && map[n.y as usize][n.x as usize] == Block::Scaffold 
-                    {
-                        neighbors += 1;
-                    }
-                }
+    for (x, y, block) in map.enumerate() {
+        if *block == Block::Scaffold {
+            let neighbors = map.neighbour_cells(&Vec2u::new(x, y), Neighbourhood::FOUR)
+                .filter(|(_, b)| **b == Block::Scaffold)
+                .count();
 
-                if neighbors == 4 {
-                    let d = p.x * p.y;
-                    alignment += d;
-                }
+            if neighbors == 4 {
+                alignment += x * y;
             }
         }
     }
@@ -105,13 +82,11 @@ fn find_path(mut machine: IntCodeRunner) -> Vec<String> {
     println!("Total alignment parameters: {}", alignment);
 
     // Find the robot
-    let (mut robot_pos, mut robot_dir) = map.iter().enumerate().flat_map(|(y, v)| {
-        v.iter().enumerate().filter_map(move |(x, b)| {
-            match b {
-                Block::Robot(dir) => Some((Vec2i::new(x as i32, y as i32), dir.clone())),
-                _ => None
-            }
-        })
+    let (mut robot_pos, mut robot_dir) = map.enumerate().filter_map(|(x, y, b)| {
+        match b {
+            Block::Robot(dir) => Some((Vec2i::new(x as i32, y as i32), *dir)),
+            _ => None
+        }
     }).nth(0).unwrap();
 
     println!("Bot at {:?} facing {:?}", robot_pos, robot_dir);
@@ -122,34 +97,28 @@ fn find_path(mut machine: IntCodeRunner) -> Vec<String> {
 
     let mut travel = Vec::new();
     let mut r_pos = robot_pos.clone();
-    let mut r_dir = robot_dir.clone();
+    let mut r_dir = robot_dir;
 
     loop {
         let mut move_to = None;
 
         // Search for the next position
-        // But search the directions nearest to your current first
-        let r_dir_im = r_dir.clone();
-        let mut sorted_dirs = directions.clone();
-        sorted_dirs.sort_by_key(|v| r_dir_im.sub(v).sqLength());
-        
-        for dir in sorted_dirs.iter().take(3) {
-            let n = r_pos.add(dir);
-
-            if n.x >= 0 && (n.x as usize) < len_x && n.y >= 0 && (n.y as usize) < len_y 
-                && map[n.y as usize][n.x as usize] == Block::Scaffold
-            {
+        // But keep going straight if we can, and never turn back
+        for dir in [r_dir, r_dir.turn_left(), r_dir.turn_right()].iter() {
+            let n = &r_pos + &Vec2i::from(*dir);
+
+            if map.getvi(&n) == Some(&Block::Scaffold) {
                 // Use this direction
-                move_to = Some(n);
+                move_to = Some((n, *dir));
                 break;
             }
         }
 
-        if let Some(move_to) = move_to {
-            r_dir = move_to.sub(&r_pos);
+        if let Some((move_to, dir)) = move_to {
+            r_dir = dir;
             r_pos = move_to;
 
-            travel.push(r_dir.clone());
+            travel.push(r_dir);
         } else {
             break; // No more moves
         }
@@ -160,7 +129,7 @@ fn find_path(mut machine: IntCodeRunner) -> Vec<String> {
     // Now need to convert to a vector of strings describing the action
     let mut instructions : Vec<String> = Vec::new();
 
-    let mut r_dir = robot_dir.clone();
+    let mut r_dir = robot_dir;
     let mut run_len = 0;
 
     for dir in travel {
@@ -172,17 +141,10 @@ fn find_path(mut machine: IntCodeRunner) -> Vec<String> {
             }
 
             // Need to rotate to this direction
-            let idx_aim = directions.iter().position(|v| *v == dir).unwrap() as i32;
-            let idx_cur = directions.iter().position(|v| *v == r_dir).unwrap() as i32;
-
-            let change = idx_aim - idx_cur;
-
-            let c = match change {
-                1 => "L",
-                -1 => "R",
-                3 => "R",
-                -3 => "L",
-                _ => panic!("Direction index change from {} to {} (change={}) should not be possible!", idx_cur, idx_aim, change)
+            let c = match r_dir.turn_to(dir) {
+                Turn::Left => "L",
+                Turn::Right => "R",
+                turn => panic!("Turn {:?} from {:?} to {:?} should not be possible!", turn, r_dir, dir)
             };
             instructions.push(c.to_string());
 
@@ -260,13 +222,13 @@ fn try_chomp(instructions: &Vec<String>, run_layout: [usize; 3]) -> Option<Vec<S
 }
 
 fn main() {
-    let (itx, irx) = channel::<i32>();
+    let (_itx, irx) = channel::<i64>();
 
     let contents = String::from_utf8_lossy(include_bytes!("../input.txt")).to_string();
     //let contents = std::fs::read_to_string("input.txt").expect("Couldn't read file");
     //let mut machine = IntCodeRunner::load_file(contents, irx);
     
-    let instructions = find_path(IntCodeRunner::load_file(contents.clone(), irx));
+    let instructions = find_path(IntCodeRunner::new(IntCodeMachine::load_file(contents.clone()), irx));
 
     // Chunk into L,5 etc
     let instructions: Vec<String> = instructions.iter().chunks(2).into_iter().map(|chunk| {
@@ -304,19 +266,19 @@ fn main() {
     let input_chars = input_chars + "\nn\n";
     println!("\nInput: \n{}\n", input_chars);
 
-    let (itx, irx) = channel::<i32>();
+    let (itx, irx) = channel::<i64>();
 
     // Run the machine!
-    let mut machine = IntCodeRunner::load_file(contents, irx);
+    let mut machine = IntCodeRunner::new(IntCodeMachine::load_file(contents), irx);
 
     // Wake up
     machine.machine.store(0, 2);
 
     for c in input_chars.chars() {
-        itx.send(c as i32).unwrap();
+        itx.send(c as i64).unwrap();
     }
 
-    while let Some(i) = machine.next() {
+    while let IntCodeIO::Output(i) = machine.next() {
         if i > 0 && i < 120 {
             print!("{}", (i as u32 & 0xFF) as u8 as char);
         } else {
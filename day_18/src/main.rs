@@ -148,15 +148,15 @@ fn main() {
     let (x, y) = map.position(|b| *b == Block::Bot).unwrap();
 
     // Augment the input
-    *map.get_mut(x, y).unwrap() = Block::Wall;
-    *map.get_mut(x + 1, y).unwrap() = Block::Wall;
-    *map.get_mut(x - 1, y).unwrap() = Block::Wall;
-    *map.get_mut(x, y + 1).unwrap() = Block::Wall;
-    *map.get_mut(x, y - 1).unwrap() = Block::Wall;
-    *map.get_mut(x + 1, y + 1).unwrap() = Block::Bot;
-    *map.get_mut(x + 1, y - 1).unwrap() = Block::Bot;
-    *map.get_mut(x - 1, y + 1).unwrap() = Block::Bot;
-    *map.get_mut(x - 1, y - 1).unwrap() = Block::Bot;
+    map[(x, y)] = Block::Wall;
+    map[(x + 1, y)] = Block::Wall;
+    map[(x - 1, y)] = Block::Wall;
+    map[(x, y + 1)] = Block::Wall;
+    map[(x, y - 1)] = Block::Wall;
+    map[(x + 1, y + 1)] = Block::Bot;
+    map[(x + 1, y - 1)] = Block::Bot;
+    map[(x - 1, y + 1)] = Block::Bot;
+    map[(x - 1, y - 1)] = Block::Bot;
     let best = best_path(map.clone());
     println!("Part2: best? = {}", best);
 }
@@ -1,5 +1,6 @@
 use common::vec2::{Vec2i, Vec2u};
-use common::array2d::Array2D;
+use common::array2d::{Array2D, FromGridChar, Neighbourhood};
+use common::search::grid_bfs;
 
 use std::convert::TryFrom;
 use std::collections::{HashMap, HashSet, BTreeSet, VecDeque};
@@ -192,35 +193,21 @@ impl Maze {
     }
 }
 
-fn iter_directions() -> std::vec::IntoIter<Vec2i> {
-    vec![
-        Vec2i::new(0, 1),
-        Vec2i::new(0, -1),
-        Vec2i::new(1, 0),
-        Vec2i::new(-1, 0)
-    ].into_iter()
+impl FromGridChar for Block {
+    fn from_grid_char(c: char) -> Option<Self> {
+        match c {
+            '.' => Some(Block::Air),
+            '#' => Some(Block::Wall),
+            '@' => Some(Block::Bot),
+            'a'..='z' => Some(Block::Key(c.to_ascii_uppercase())),
+            'A'..='Z' => Some(Block::Door(c)),
+            _ => None
+        }
+    }
 }
 
 pub fn read_map(contents: String) -> Array2D<Block> {
-    let map: Vec<Vec<Block>> = contents.split("\n").map(|line| {
-        line.trim().chars().map(|c| {
-            match c {
-                '.' => Block::Air,
-                '#' => Block::Wall,
-                '@' => Block::Bot,
-                _ => {
-                    let up = c.to_uppercase().nth(0).unwrap();
-                    if up == c {
-                        Block::Door(c)
-                    } else {
-                        Block::Key(up)
-                    }
-                }
-            }
-        }).collect()
-    }).collect();
-
-    Array2D::from(map)
+    Array2D::parse_trimmed(&contents).unwrap_or_else(|e| panic!("{}", e))
 }
 
 fn visit_block(
@@ -239,13 +226,9 @@ fn visit_block(
         target: pos.clone()
     };
 
-    let neighbors = iter_directions().filter(|dir| {
-        if let Some(Block::Wall) = map.getvi(&pos.add(dir)) {
-            false
-        } else {
-            true
-        }
-    }).count();
+    let neighbors = map.neighboursvi(pos, Neighbourhood::FOUR)
+        .filter(|p| map.getvi(p) != Some(&Block::Wall))
+        .count();
 
     match map.getvi(pos) {
         Some(Block::Key(c)) => {
@@ -284,9 +267,7 @@ fn map_to_tree(map: &Array2D<Block>, start: &Vec2i) -> (HashMap<Vec2i, NodeType>
     let mut cur_pos = start.clone();
 
     'search: loop {
-        'try_dirs: for dir in iter_directions() {
-            let next_p = cur_pos.add(&dir);
-
+        'try_dirs: for next_p in map.neighboursvi(&cur_pos, Neighbourhood::FOUR) {
             if !visited.contains(&next_p) {
                 if let Some(Block::Wall) = map.getvi(&next_p) {
                     continue 'try_dirs;
@@ -320,78 +301,18 @@ fn map_to_tree(map: &Array2D<Block>, start: &Vec2i) -> (HashMap<Vec2i, NodeType>
 }
 
 fn shortest_paths_from(map: &Array2D<Block>, start: &Vec2i, rid: char) -> HashMap<char, usize> {
-    let mut shortest_paths = HashMap::new();
-
-    // Stack of current depth search - top of stack is always current position
-    let mut search_stack = Vec::<Vec2i>::new();
-
-    // All visited nodes and the distance to them
-    let mut visited = HashMap::<Vec2i, usize>::new();
-
-    let directions = vec![
-        Vec2i::new(0, 1),
-        Vec2i::new(0, -1),
-        Vec2i::new(1, 0),
-        Vec2i::new(-1, 0)
-    ];
-
-    let mut curpos = start.clone();
-
-    visited.insert(curpos.clone(), 0);
-
-    // Iterate until no more nodes to visit
-    'search: loop {
-        let next_dist = search_stack.len() + 1;
-
-        'trying_dirs: for dir in &directions {
-            let next_p = curpos.add(dir);
-
-            if let Ok(next_p_u) = Vec2u::try_from(next_p.clone()) {
-                // Is this a valid node
-                if let Some(node) = map.getv(&next_p_u) {
-                    // Check this isn't a wall
-                    if Block::Wall == *node {
-                        continue 'trying_dirs;
-                    }
-
-                    // Get current best distance to here (default very large)
-                    let best_dist = if let Some(d) = visited.get(&next_p) {
-                        *d
-                    } else {
-                        100000000
-                    };
-                    
-                    // Check this is a better path than any previous
-                    if next_dist < best_dist {
-                        // Record that we've visited
-                        visited.insert(next_p.clone(), next_dist);
-                        search_stack.push(curpos);
-                        curpos = next_p;
-
-                        match node {
-                            // This is a key or bot add to the shortest path list
-                            Block::Key(c) => { shortest_paths.insert(*c, next_dist); }
-                            Block::Bot => { shortest_paths.insert(rid, next_dist); }
-                            _ => {}
-                        }
-
-                        continue 'search;
-                    }
-                }
-            }
-        }
-
-        // We didnt find a direction (because we didn't continue 'search) so we 
-        // must backtrack instead
-        if let Some(d) = search_stack.pop() {
-            curpos = d;
-        } else {
-            // At start with nowhere to go - we're done
-            break 'search;
-        }
-    }
-
-    return shortest_paths;
+    let start = Vec2u::try_from(start.clone()).unwrap();
+    let search = grid_bfs(map, &start, |b| *b != Block::Wall);
+
+    // Keys and bots reachable from here (but not the one we're starting on)
+    search.distances.iter()
+        .filter(|(p, _)| **p != start)
+        .filter_map(|(p, d)| match map[p] {
+            Block::Key(c) => Some((c, *d)),
+            Block::Bot => Some((rid, *d)),
+            _ => None
+        })
+        .collect()
 }
 
 // Remove unnecessary junctions with nothing below them
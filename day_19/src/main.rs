@@ -88,9 +88,9 @@ fn main() {
     let mut square = HashSet::<Vec2l>::new();
 
     for lft in left_beam_edge {
-        let tr = lft.add(&upr);
+        let tr = &lft + &upr;
         if beam.contains(&tr) {
-            let tl = lft.add(&Vec2l::new(0, -1 * (sq_size - 1)));
+            let tl = &lft + &Vec2l::new(0, -1 * (sq_size - 1));
             println!("Found beam at {:?}", tl);
 
             let p = tl.x*10000 + tl.y;
@@ -98,7 +98,7 @@ fn main() {
 
             for x in 0..sq_size {
                 for y in 0..sq_size {
-                    //square.insert(lft.add(&Vec2l::new(x, -1 * y)));
+                    //square.insert(&lft + &Vec2l::new(x, -1 * y));
                 }
             }
             break;
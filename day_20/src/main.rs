@@ -1,5 +1,6 @@
 use common::vec2::{Vec2i, Vec2u};
-use common::array2d::Array2D;
+use common::array2d::{Array2D, FromGridChar, Neighbourhood, ToGridChar};
+use common::search::bfs;
 
 use std::collections::{
     HashMap, VecDeque, HashSet
@@ -13,49 +14,41 @@ enum Block {
     HalfPortal(char)
 }
 
-impl std::fmt::Display for Block {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        let c = match self {
-            Block::Air => "  ",
-            Block::Wall => "██",
-            Block::Portal(s) => &s,
-            Block::HalfPortal(c) => "~~"
-        };
-
-        write!(f, "{}", c)
+impl ToGridChar for Block {
+    fn to_grid_char(&self) -> char {
+        match self {
+            Block::Air => '.',
+            Block::Wall => '#',
+            Block::Portal(_) => '@',
+            Block::HalfPortal(c) => *c
+        }
     }
 }
 
-fn read_map(contents: String) -> Array2D<Block> {
-    let map: Vec<Vec<Block>> = contents.split("\n").map(|line| {
-        line.chars().map(|c| {
-            match c {
-                '.' => Block::Air,
-                ' ' => Block::Wall,
-                '#' => Block::Wall,
-                'A'..='Z' => Block::HalfPortal(c),
-                _ => Block::Wall
-            }
-        }).collect()
-    }).collect();
-
-    Array2D::from(map)
+impl FromGridChar for Block {
+    fn from_grid_char(c: char) -> Option<Self> {
+        match c {
+            '.' => Some(Block::Air),
+            ' ' | '#' => Some(Block::Wall),
+            'A'..='Z' => Some(Block::HalfPortal(c)),
+            _ => None
+        }
+    }
 }
 
-fn get_compass_directions() -> Vec<Vec2i> {
-    vec![
-        Vec2i::new(0, 1),
-        Vec2i::new(0, -1),
-        Vec2i::new(1, 0),
-        Vec2i::new(-1, 0),
-    ]
+fn read_map(contents: String) -> Array2D<Block> {
+    // Trailing spaces are only padding, and the input's last line has one more
+    // than the rest, so square the lines off before parsing
+    let width = contents.lines().map(|line| line.trim_end().len()).max().unwrap_or(0);
+    let contents: String = contents.lines()
+        .map(|line| format!("{:width$}\n", line.trim_end(), width = width))
+        .collect();
+    Array2D::parse(&contents).unwrap_or_else(|e| panic!("{}", e))
 }
 
 /// Find HalfPortals and turn them into portals
 /// Return location of portals
 fn fix_portals(map: &mut Array2D<Block>) -> HashMap<Vec2i, String> {
-    let directions = get_compass_directions();
-    
     let mut portals = HashMap::new();
 
     for y in 0..map.len_y {
@@ -72,10 +65,8 @@ fn fix_portals(map: &mut Array2D<Block>) -> HashMap<Vec2i, String> {
                 let mut p2 = None;
                 let mut p2pos = Vec2i::new(0, 0);
 
-                for dir in &directions {
-                    let np = pos.add(dir);
-
-                    match map.getvi_mut(&np) {
+                for np in map.neighboursvi(&pos, Neighbourhood::FOUR) {
+                    match map.getvi(&np) {
                         Some(Block::HalfPortal(p)) => {
                             p2 = Some(*p);
                             p2pos = np;
@@ -118,8 +109,6 @@ fn fix_portals(map: &mut Array2D<Block>) -> HashMap<Vec2i, String> {
 // Produce a map of Vec2i portals (i.e. if you step on a you end up at b)
 // (outer, inner)
 fn portal_associations(map: &Array2D<Block>, portals: &HashMap<Vec2i, String>) -> (HashMap<Vec2i, Vec2i>, HashMap<Vec2i, Vec2i>) {
-    let directions = get_compass_directions();
-
     let mut outer_assoc = HashMap::new();
     let mut inner_assoc = HashMap::new();
 
@@ -129,18 +118,18 @@ fn portal_associations(map: &Array2D<Block>, portals: &HashMap<Vec2i, String>) -
         // Find the other portal
         if let Some((other_pos, _)) = portals.iter().find(|(k, v)| *k != position && *v == portal) {
             // Find the air adjacent to this portal
-            let dir = directions.iter().find(|dir| {
-                map.getvi(&other_pos.add(dir)) == Some(&Block::Air)
-            }).expect("Could not find an adjacent air block");
+            let exit = map.neighboursvi(other_pos, Neighbourhood::FOUR)
+                .find(|p| map.getvi(p) == Some(&Block::Air))
+                .expect("Could not find an adjacent air block");
 
             // Portal jumps from position -> air adjacent to pair
-            let radius = position.sub(&midpoint).abs();
+            let radius = (position - &midpoint).abs();
             if radius.x > midpoint.x - 4 || radius.y > midpoint.y - 4 {
                 // This is an outer portal
-                outer_assoc.insert(position.clone(), other_pos.add(dir));
+                outer_assoc.insert(position.clone(), exit);
             } else {
                 // This is an inner portal
-                inner_assoc.insert(position.clone(), other_pos.add(dir));
+                inner_assoc.insert(position.clone(), exit);
             }
         } else {
             println!("No matching portal pair for portal {} at pos {:?}", portal, position);
@@ -161,102 +150,42 @@ fn shortest_path(
         outer_assoc: &HashMap<Vec2i, Vec2i>, 
         inner_assoc: &HashMap<Vec2i, Vec2i>) -> usize
 {
-    let visit_init = Array2D::<usize>::with_shape(map.len_x, map.len_y, &std::usize::MAX);
-
-    // Produce 15 layers of visited map (this is arbitrary, we're guessing it wont be more than this)
-    let mut visited = Vec::<Array2D<usize>>::new();
-    for _ in 0..100 {
-        visited.push(visit_init.clone());
-    }
-
-    let directions = get_compass_directions();
-
-    let mut search_stack = Vec::<(Vec2i, usize)>::new();
-
-    let mut cur_pos = start.clone();
-    let mut cur_depth = 0;
-
-    'searching: loop {
-        'try_dir: for dir in &directions {
-            let mut next_pos = cur_pos.add(dir);
-            let mut next_depth = cur_depth;
-
-            // If it's a wall, we cannot
-            if let Some(Block::Wall) = map.getvi(&next_pos) {
-                continue 'try_dir;
-            }
-
-            // If it's an outer portal, jump out of the stack
-            if let Some(jmp) = outer_assoc.get(&next_pos) {
-                next_pos = jmp.clone();
-
-                if cur_depth == 0 {
-                    // Cannot jump out on top level
-                    continue 'try_dir;
-                }
-
-                next_depth = cur_depth - 1;
-            }
-
-            // If it's an inner portal, jump down the stack
-            if let Some(jmp) = inner_assoc.get(&next_pos) {
-                next_pos = jmp.clone();
-
-                if cur_depth + 1 >= visited.len() {
-                    // Cannot jump as we're at the bottom level
-                    continue 'try_dir;
-                }
-
-                next_depth = cur_depth + 1;
-            }
-
-            if let Some(dist) = visited[next_depth].getvi_mut(&next_pos) {
-                if search_stack.len() + 1 < *dist {
-                    *dist = search_stack.len() + 1;
-                    search_stack.push((cur_pos, cur_depth));
-                    cur_pos = next_pos;
-                    cur_depth = next_depth;
-                    continue 'searching;
+    // How many levels deep to search (this is arbitrary, we're guessing it wont be more than this)
+    let max_depth = 100;
+
+    let search = bfs((start.clone(), 0), |(cur_pos, cur_depth)| {
+        let cur_depth = *cur_depth;
+        map.neighboursvi(cur_pos, Neighbourhood::FOUR).filter_map(move |next_pos| {
+            match map.getvi(&next_pos) {
+                // If it's a wall (or off the map), we cannot
+                None | Some(Block::Wall) => None,
+                _ => {
+                    if let Some(jmp) = outer_assoc.get(&next_pos) {
+                        // If it's an outer portal, jump out of the stack
+                        // Cannot jump out on top level
+                        if cur_depth == 0 {
+                            None
+                        } else {
+                            Some((jmp.clone(), cur_depth - 1))
+                        }
+                    } else if let Some(jmp) = inner_assoc.get(&next_pos) {
+                        // If it's an inner portal, jump down the stack
+                        // Cannot jump as we're at the bottom level
+                        if cur_depth + 1 >= max_depth {
+                            None
+                        } else {
+                            Some((jmp.clone(), cur_depth + 1))
+                        }
+                    } else {
+                        Some((next_pos, cur_depth))
+                    }
                 }
             }
-        }
-
-        // No new position found, backtrack one
-        if let Some((pos, depth)) = search_stack.pop() {
-            cur_pos = pos;
-            cur_depth = depth;
-        } else {
-            // No more places to search, end.
-            break;
-        }
-    }
-
-    // for i in 0..10 {
-    //     println!("Level: {}", i);
-
-    //     for y in 0..map.len_y {
-    //         for x in 0..map.len_x {
-    //             let mut d = visited[i].get(x, y).unwrap();
-    //             if Some(&Block::Wall) == map.get(x, y) {
-    //                 print!("███");
-    //             } else if *d == std::usize::MAX {
-    //                 print!("   ");
-    //             } else if *d > 999 {
-    //                 print!("+++");
-    //             } else {
-    //                 print!("{:03}", d);
-    //             }
-    //         }
-
-    //         println!("");
-    //     }
-
-    //     println!("");
-    // }
+        }).collect::<Vec<_>>()
+    });
 
-    // Now that the shortest path map is complete, return the end distance
-    // also has to be end distance at the top level
-    return *visited[0].getvi(end).unwrap();
+    // The end has to be reached at the top level
+    return search.distance(&(end.clone(), 0)).unwrap();
 }
 
 fn main() {
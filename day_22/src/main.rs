@@ -1,8 +1,4 @@
-use modinverse::modinverse;
-use num::bigint::BigInt;
-use num::bigint::ToBigInt;
-use num::cast::ToPrimitive;
-use num::traits::{One, Zero};
+use common::math::{mod_inverse, mod_mul};
 
 #[derive(Clone)]
 enum Instruction {
@@ -78,11 +74,8 @@ fn num_at(deck_len: i64, pos: i64, instructions: &Vec<Instruction>) -> i64 {
             },
             Instruction::NewStack => deck_len - pos - 1,
             Instruction::DealIncrement(n) => {
-                let mod_inv = modinverse(n as i64, deck_len).expect("No mod inverse exists!");
-                let pos = pos.to_bigint().unwrap();
-                let mod_inv = mod_inv.to_bigint().unwrap();
-                let deck_len = deck_len.to_bigint().unwrap();
-                ((pos * mod_inv) % deck_len).to_i64().unwrap()
+                let mod_inv = mod_inverse(n as i128, deck_len as i128).expect("No mod inverse exists!");
+                mod_mul(pos as i128, mod_inv, deck_len as i128) as i64
             }
         }
     }
@@ -91,7 +84,7 @@ fn num_at(deck_len: i64, pos: i64, instructions: &Vec<Instruction>) -> i64 {
 }
 
 // Formulate an inverse function
-fn formulate_inverse(deck_len: i64, instructions: &Vec<Instruction>) -> (BigInt, BigInt) {
+fn formulate_inverse(deck_len: i64, instructions: &Vec<Instruction>) -> (i128, i128) {
     // Can represent the value at any point by
     //      x' = ax + b % deck_len
     // Where x is the original input (the target position)
@@ -99,14 +92,15 @@ fn formulate_inverse(deck_len: i64, instructions: &Vec<Instruction>) -> (BigInt,
     // Initially a = 1, b = 0
     // All operations can be considered in (mod deck_len) space.
     
-    let mut a = 1.to_bigint().unwrap();
-    let mut b = 0.to_bigint().unwrap();
+    let m = deck_len as i128;
+    let mut a = 1;
+    let mut b = 0;
 
     for ins in instructions.iter().rev() {
         match ins {
             Instruction::Cut(n) => {
                 // x' = x + n
-                b += n.to_bigint().unwrap();
+                b = (b + *n as i128) % m;
             }
             Instruction::NewStack => {
                 // x' = deck_len - 1 - x
@@ -115,47 +109,46 @@ fn formulate_inverse(deck_len: i64, instructions: &Vec<Instruction>) -> (BigInt,
                 b *= -1;
 
                 // then add deck_len - 1
-                b += deck_len - 1;
+                b = (b + m - 1) % m;
             }
             Instruction::DealIncrement(inc) => {
                 // x' = mod_inv * x
-                let mod_inv = modinverse(*inc as i64, deck_len).expect("No mod inverse exists!");
-                a *= mod_inv;
-                b *= mod_inv;
+                let mod_inv = mod_inverse(*inc as i128, m).expect("No mod inverse exists!");
+                a = mod_mul(a, mod_inv, m);
+                b = mod_mul(b, mod_inv, m);
             }
         }
     }
 
-    let dl = deck_len.to_bigint().unwrap();
-    (a % dl.clone(), b % dl.clone())
+    (a.rem_euclid(m), b.rem_euclid(m))
 }
 
-fn convolve_funcs(m: &BigInt, f1: &(BigInt, BigInt), f2: &(BigInt, BigInt)) -> (BigInt, BigInt) {
+fn convolve_funcs(m: i128, f1: &(i128, i128), f2: &(i128, i128)) -> (i128, i128) {
     let (a1, b1) = f1;
     let (a2, b2) = f2;
 
-    ((a1 * a2) % m, (a1 * b2 + b1) % m)
+    (mod_mul(*a1, *a2, m), (mod_mul(*a1, *b2, m) + b1) % m)
 }
 
 // Raise a function f(x) = ax + b (mod m)
 // to nth power using power lookups
-fn nth_power(m: &BigInt, f: &(BigInt, BigInt), n: u64) -> (BigInt, BigInt) {
+fn nth_power(m: i128, f: &(i128, i128), n: u64) -> (i128, i128) {
     // Create a power lookup table of 2^1 to 2^64
-    let mut powers = Vec::<(BigInt, BigInt)>::with_capacity(64);
+    let mut powers = Vec::<(i128, i128)>::with_capacity(64);
 
-    let mut f = f.clone();
+    let mut f = *f;
     for _ in 0..64 {
-        powers.push(f.clone());
-        f = convolve_funcs(&m, &f, &f);
+        powers.push(f);
+        f = convolve_funcs(m, &f, &f);
     }
 
-    let mut base : (BigInt, BigInt) = (One::one(), Zero::zero());
+    let mut base : (i128, i128) = (1, 0);
 
     let mut n = n;
     for pwr in powers {
         if n & 0b1 == 0b1 {
             // This bit is active, therefore this power is applied
-            base = convolve_funcs(&m, &pwr, &base);
+            base = convolve_funcs(m, &pwr, &base);
         }
 
         n >>= 1;
@@ -164,9 +157,9 @@ fn nth_power(m: &BigInt, f: &(BigInt, BigInt), n: u64) -> (BigInt, BigInt) {
     base
 }
 
-fn apply(m: BigInt, f: (BigInt, BigInt), x: BigInt) -> BigInt {
+fn apply(m: i128, f: (i128, i128), x: i128) -> i128 {
     let (a, b) = f;
-    (a * x + b) % m
+    (mod_mul(a, x, m) + b) % m
 }
 
 fn main() {
@@ -184,11 +177,9 @@ fn main() {
 
     let f = formulate_inverse(deck_len, &ins);
 
-    let deck_len = deck_len.to_bigint().unwrap();
+    let fpwr = nth_power(deck_len as i128, &f, 101741582076661);
 
-    let fpwr = nth_power(&deck_len, &f, 101741582076661);
-
-    let r = apply(deck_len, fpwr, 2020.to_bigint().unwrap());
+    let r = apply(deck_len as i128, fpwr, 2020);
     println!("Answer: {}", r);
 }
 
@@ -306,18 +297,18 @@ mod tests {
 
         println!("Inverse x' (mod {}) = {}x + {}", deck_len, a, b);
 
-        let at = (2020.to_bigint().unwrap() * a + b) % deck_len.to_bigint().unwrap();
+        let at = apply(deck_len as i128, (a, b), 2020);
         println!("With formula: {}", at);
 
-        assert_eq!(16559917466694i64.to_bigint().unwrap(), at);
+        assert_eq!(16559917466694, at);
     }
 
     #[test]
     fn test_nth_power() {
-        let f = (4.to_bigint().unwrap(), 3.to_bigint().unwrap());
+        let f = (4, 3);
 
-        let (a, b) = nth_power(40.to_bigint().unwrap(), f, 3);
-        assert_eq!(Some(24), a.to_i64());
-        assert_eq!(Some(23), b.to_i64());
+        let (a, b) = nth_power(40, &f, 3);
+        assert_eq!(24, a);
+        assert_eq!(23, b);
     }
 }
\ No newline at end of file
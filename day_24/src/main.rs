@@ -1,31 +1,37 @@
 mod devec;
 
-use common::vec2::Vec2i;
-use common::array2d::Array2D;
+use common::vec2::{Vec2i, Vec2u};
+use common::array2d::{Array2D, FromGridChar, Neighbourhood, ToGridChar};
+use common::cycle;
 use devec::Devec;
 
-use std::collections::HashSet;
-
 #[derive(PartialEq, Clone, Debug)]
 enum Tile {
     Bug,
     Air
 }
 
-fn load_map(contents: String) -> Array2D<Tile> {
-    let map : Vec<Vec<Tile>> = contents.split("\n")
-        .map(|s| s.trim()).filter(|s| s.len() > 0)
-        .map(|s| {
-            s.chars().map(|c| {
-                match c {
-                    '.' => Tile::Air,
-                    '#' => Tile::Bug,
-                    _ => panic!("Unrecognised character {}", c)
-                }
-            }).collect()
-        }).collect();
+impl FromGridChar for Tile {
+    fn from_grid_char(c: char) -> Option<Self> {
+        match c {
+            '.' => Some(Tile::Air),
+            '#' => Some(Tile::Bug),
+            _ => None
+        }
+    }
+}
+
+impl ToGridChar for Tile {
+    fn to_grid_char(&self) -> char {
+        match self {
+            Tile::Air => '.',
+            Tile::Bug => '#'
+        }
+    }
+}
 
-    Array2D::from(map)
+fn load_map(contents: String) -> Array2D<Tile> {
+    Array2D::parse_trimmed(&contents).unwrap_or_else(|e| panic!("{}", e))
 }
 
 fn biodiversity(map: &Array2D<Tile>) -> usize {
@@ -42,55 +48,32 @@ fn biodiversity(map: &Array2D<Tile>) -> usize {
 fn step(map: &mut Array2D<Tile>) {
     let last = map.clone();
 
-    let directions = vec![
-        Vec2i::new(0, 1),
-        Vec2i::new(0, -1),
-        Vec2i::new(1, 0),
-        Vec2i::new(-1, 0)
-    ];
-
-    let mx = map.len_x as i32;
-    let my = map.len_y as i32;
+    for (x, y, old) in last.enumerate() {
+        let adjacent = last.neighbour_cells(&Vec2u::new(x, y), Neighbourhood::FOUR)
+            .filter(|(_, t)| **t == Tile::Bug)
+            .count();
 
-    for x in 0..mx {
-        for y in 0..my {
-            let p = Vec2i::new(x, y);
+        let tile = &mut map[(x, y)];
 
-            let mut adjacent = 0;
-            for dir in &directions {
-                if Some(&Tile::Bug) == last.getvi(&p.add(dir)) {
-                    adjacent += 1;
-                }
+        if *old == Tile::Bug {
+            if adjacent != 1 {
+                *tile = Tile::Air;
             }
-
-            let tile = map.getvi_mut(&p).unwrap();
-
-            if *tile == Tile::Bug {
-                if adjacent != 1 {
-                    *tile = Tile::Air;
-                }
-            } else {
-                if adjacent == 1 || adjacent == 2 {
-                    *tile = Tile::Bug;
-                }
+        } else {
+            if adjacent == 1 || adjacent == 2 {
+                *tile = Tile::Bug;
             }
         }
     }
 }
 
-fn first_appears_twice(mut map: Array2D<Tile>) -> usize {
-    let mut seen = HashSet::<usize>::new();
-
-    loop {
-        let bio = biodiversity(&map);
-
-        if seen.contains(&bio) {
-            return bio;
-        }
-
-        seen.insert(bio);
-        step(&mut map);
-    }
+fn first_appears_twice(map: Array2D<Tile>) -> usize {
+    let cycle = cycle::brent(map, |m| {
+        let mut m = m.clone();
+        step(&mut m);
+        m
+    });
+    biodiversity(&cycle.state)
 }
 
 fn count_bugs(map: &Array2D<Tile>) -> usize {
@@ -123,7 +106,7 @@ fn step_level(upper: &Array2D<Tile>, last: &Array2D<Tile>, lower: &Array2D<Tile>
 
             let mut adjacent = 0;
             for dir in &directions {
-                if Some(&Tile::Bug) == last.getvi(&p.add(dir)) {
+                if Some(&Tile::Bug) == last.getvi(&(&p + dir)) {
                     adjacent += 1;
                 }
             }
@@ -191,7 +174,7 @@ fn step_level(upper: &Array2D<Tile>, last: &Array2D<Tile>, lower: &Array2D<Tile>
     }
 
     // Must always keep centre as air so it doesnt count
-    *out.get_mut(2, 2).unwrap() = Tile::Air;
+    out[(2, 2)] = Tile::Air;
 }
 
 fn step_multilevel(levels: &Devec<Array2D<Tile>>) -> Devec<Array2D<Tile>> {
@@ -235,17 +218,7 @@ fn print_levels(levels: &Devec<Array2D<Tile>>) {
     for i in levels.min_idx()..=levels.max_idx() {
         println!("Level = {}", i);
 
-        for y in 0..5 {
-            for x in 0..5 {
-                print!("{}", if Some(&Tile::Bug) == levels.get(i).unwrap().get(x, y) {
-                    '#'
-                } else {
-                    '.'
-                });
-            }
-            println!("");
-        }
-
+        print!("{}", levels.get(i).unwrap().to_grid_string());
         println!("");
     }
 }